@@ -1,8 +1,8 @@
 use std::hint::black_box;
 
-use criterion::{self, BenchmarkId, Criterion, criterion_group, criterion_main};
+use criterion::{self, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
 
-use huscee::*;
+use huscee::{ordering::Heuristics, tt::TranspositionTable, *};
 
 pub fn perft_benchmark(c: &mut Criterion) {
     let board = Board::start_pos();
@@ -16,17 +16,56 @@ pub fn perft_benchmark(c: &mut Criterion) {
     group.finish();
 }
 
+pub fn perft_kiwipete_benchmark(c: &mut Criterion) {
+    let board =
+        Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+            .unwrap();
+    let mut group = c.benchmark_group("perft_kiwipete");
+    for depth in 1..4usize {
+        group.bench_with_input(BenchmarkId::from_parameter(depth), &depth, |b, &depth| {
+            b.iter(|| board.perft(black_box(depth)))
+        });
+    }
+
+    group.finish();
+}
+
+/// Searches the start position from scratch, deepening one ply at a time like the engine does,
+/// and returns how many nodes it took.
+fn negamax_nodes(tt: &mut TranspositionTable, heuristics: &mut Heuristics, depth: u8) -> u64 {
+    tt.clear();
+    heuristics.clear();
+
+    let mut search = Search::new(Game::start_pos(), tt, heuristics, u128::MAX);
+    let mut moves = search.root_moves();
+    for depth in 1..=depth {
+        let _ = search.root(&mut moves, depth);
+    }
+    search.nodes
+}
+
 pub fn negamax_benchmark(c: &mut Criterion) {
-    let board = Board::start_pos();
+    let mut tt = TranspositionTable::default();
+    let mut heuristics = Heuristics::new();
     let mut group = c.benchmark_group("negamax");
     for depth in 1..5 {
+        // Move ordering shows in the node count more directly than in the time
+        let nodes = negamax_nodes(&mut tt, &mut heuristics, depth);
+        println!("negamax/{depth}: {nodes} nodes");
+        group.throughput(Throughput::Elements(nodes));
+
         group.bench_with_input(BenchmarkId::from_parameter(depth), &depth, |b, &depth| {
-            b.iter(|| negamax(f32::NEG_INFINITY, f32::INFINITY, depth, &board))
+            b.iter(|| negamax_nodes(&mut tt, &mut heuristics, black_box(depth)))
         });
     }
 
     group.finish();
 }
 
-criterion_group!(benches, perft_benchmark, negamax_benchmark);
+criterion_group!(
+    benches,
+    perft_benchmark,
+    perft_kiwipete_benchmark,
+    negamax_benchmark
+);
 criterion_main!(benches);
@@ -0,0 +1,352 @@
+//! Bitboards are `u64` sets of squares, where bit `rank * 8 + file` is set for every square in
+//! the set (see [`Coord::to_sq`]).
+
+use super::*;
+
+use std::sync::LazyLock;
+
+pub fn bit(coord: Coord) -> u64 {
+    1 << coord.to_sq()
+}
+
+/// Iterates over the squares of a bitboard, from a1 to h8.
+#[derive(Clone, Copy, Debug)]
+pub struct Bits(pub u64);
+
+impl Iterator for Bits {
+    type Item = Coord;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0 == 0 {
+            return None;
+        }
+
+        let sq = self.0.trailing_zeros() as usize;
+        self.0 &= self.0 - 1;
+
+        Some(Coord::from_sq(sq))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.0.count_ones() as usize;
+        (n, Some(n))
+    }
+}
+
+impl ExactSizeIterator for Bits {}
+
+pub static KNIGHT_ATTACKS: [u64; 64] = leaper_table(&[
+    (2, 1),
+    (2, -1),
+    (1, 2),
+    (-1, 2),
+    (-2, 1),
+    (-2, -1),
+    (-1, -2),
+    (1, -2),
+]);
+
+pub static KING_ATTACKS: [u64; 64] = leaper_table(&[
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, 1),
+    (0, -1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+]);
+
+/// The squares a pawn of the given color attacks, indexed by `Color as usize`.
+pub static PAWN_ATTACKS: [[u64; 64]; 2] = [
+    leaper_table(&[(1, 1), (1, -1)]),
+    leaper_table(&[(-1, 1), (-1, -1)]),
+];
+
+/// For every square, the set of squares reached with a single jump of one of the `(rank, file)`
+/// offsets.
+const fn leaper_table(jumps: &[(i8, i8)]) -> [u64; 64] {
+    let mut table = [0; 64];
+
+    let mut sq = 0;
+    while sq < 64 {
+        let rank = (sq / 8) as i8;
+        let file = (sq % 8) as i8;
+
+        let mut i = 0;
+        while i < jumps.len() {
+            let r = rank + jumps[i].0;
+            let f = file + jumps[i].1;
+            if r >= 0 && r < 8 && f >= 0 && f < 8 {
+                table[sq] |= 1 << (r * 8 + f);
+            }
+            i += 1;
+        }
+
+        sq += 1;
+    }
+
+    table
+}
+
+const ROOK_DIRECTIONS: [(i8, i8); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+
+/// The squares attacked by a rook on `coord`, stopping at (and including) the first occupied
+/// square in every direction.
+pub fn rook_attacks(coord: Coord, occupied: u64) -> u64 {
+    let tables = &*SLIDING_ATTACKS;
+    tables.table[tables.rook[coord.to_sq()].index(occupied)]
+}
+
+/// The squares attacked by a bishop on `coord`, stopping at (and including) the first occupied
+/// square in every direction.
+pub fn bishop_attacks(coord: Coord, occupied: u64) -> u64 {
+    let tables = &*SLIDING_ATTACKS;
+    tables.table[tables.bishop[coord.to_sq()].index(occupied)]
+}
+
+pub fn queen_attacks(coord: Coord, occupied: u64) -> u64 {
+    rook_attacks(coord, occupied) | bishop_attacks(coord, occupied)
+}
+
+/// The squares strictly between two squares on the same rank, file or diagonal. Empty if the
+/// squares aren't aligned.
+pub fn between(a: Coord, b: Coord) -> u64 {
+    if rook_attacks(a, 0) & bit(b) != 0 {
+        rook_attacks(a, bit(b)) & rook_attacks(b, bit(a))
+    } else if bishop_attacks(a, 0) & bit(b) != 0 {
+        bishop_attacks(a, bit(b)) & bishop_attacks(b, bit(a))
+    } else {
+        0
+    }
+}
+
+/// The whole rank, file or diagonal going through both squares. Empty if the squares aren't
+/// aligned.
+pub fn line(a: Coord, b: Coord) -> u64 {
+    let ends = bit(a) | bit(b);
+
+    if rook_attacks(a, 0) & bit(b) != 0 {
+        rook_attacks(a, 0) & rook_attacks(b, 0) | ends
+    } else if bishop_attacks(a, 0) & bit(b) != 0 {
+        bishop_attacks(a, 0) & bishop_attacks(b, 0) | ends
+    } else {
+        0
+    }
+}
+
+/// Builds the sliding attack tables. They are otherwise built lazily on first use, which takes
+/// long enough that it shouldn't happen in the middle of a search.
+pub fn init() {
+    LazyLock::force(&SLIDING_ATTACKS);
+}
+
+static SLIDING_ATTACKS: LazyLock<SlidingAttacks> = LazyLock::new(SlidingAttacks::generate);
+
+/// Magic numbers for one square. Multiplying the relevant occupancy by the magic number maps
+/// every occupancy to an index into the shared attack table without destructive collisions.
+struct Magic {
+    /// The squares whose occupancy affects the attacks, not including the edges of the board.
+    mask: u64,
+    magic: u64,
+    shift: u32,
+    offset: usize,
+}
+
+impl Magic {
+    fn index(&self, occupied: u64) -> usize {
+        self.offset + ((occupied & self.mask).wrapping_mul(self.magic) >> self.shift) as usize
+    }
+}
+
+struct SlidingAttacks {
+    rook: Vec<Magic>,
+    bishop: Vec<Magic>,
+    table: Vec<u64>,
+}
+
+impl SlidingAttacks {
+    fn generate() -> Self {
+        // Fixed seed, so the tables are the same on every run
+        let mut rng = 0x6d61_6769_6373_u64;
+        let mut table = Vec::new();
+
+        let rook = (0..64)
+            .map(|sq| find_magic(sq, &ROOK_DIRECTIONS, &mut table, &mut rng))
+            .collect();
+        let bishop = (0..64)
+            .map(|sq| find_magic(sq, &BISHOP_DIRECTIONS, &mut table, &mut rng))
+            .collect();
+
+        Self {
+            rook,
+            bishop,
+            table,
+        }
+    }
+}
+
+/// Walks every direction from `sq` on the given occupancy, the slow way. Used to fill the
+/// tables.
+fn ray_attacks(sq: usize, occupied: u64, directions: &[(i8, i8); 4]) -> u64 {
+    let mut attacks = 0;
+
+    for (dr, df) in directions {
+        let mut r = (sq / 8) as i8 + dr;
+        let mut f = (sq % 8) as i8 + df;
+
+        while (0..8).contains(&r) && (0..8).contains(&f) {
+            let b = 1 << (r * 8 + f);
+            attacks |= b;
+
+            // break on capture
+            if occupied & b != 0 {
+                break;
+            }
+
+            r += dr;
+            f += df;
+        }
+    }
+
+    attacks
+}
+
+fn relevant_mask(sq: usize, directions: &[(i8, i8); 4]) -> u64 {
+    const RANK_1: u64 = 0xff;
+    const RANK_8: u64 = 0xff << 56;
+    const FILE_A: u64 = 0x0101_0101_0101_0101;
+    const FILE_H: u64 = FILE_A << 7;
+
+    // A piece on the edge of the board always blocks the ray, so its occupancy doesn't matter.
+    // That is, unless the slider itself stands on that edge.
+    let rank = RANK_1 << (sq / 8 * 8);
+    let file = FILE_A << (sq % 8);
+    let edges = ((RANK_1 | RANK_8) & !rank) | ((FILE_A | FILE_H) & !file);
+
+    ray_attacks(sq, 0, directions) & !edges
+}
+
+fn xorshift(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+fn find_magic(sq: usize, directions: &[(i8, i8); 4], table: &mut Vec<u64>, rng: &mut u64) -> Magic {
+    let mask = relevant_mask(sq, directions);
+    let bits = mask.count_ones();
+    let shift = 64 - bits;
+
+    // Enumerate every subset of the mask
+    let mut occupancies = Vec::with_capacity(1 << bits);
+    let mut subset = 0u64;
+    loop {
+        occupancies.push((subset, ray_attacks(sq, subset, directions)));
+        subset = subset.wrapping_sub(mask) & mask;
+        if subset == 0 {
+            break;
+        }
+    }
+
+    let mut used = vec![0; 1 << bits];
+    let mut epoch = vec![0u32; 1 << bits];
+    let mut attempt = 0;
+
+    loop {
+        // Numbers with few bits set make good magics
+        let magic = xorshift(rng) & xorshift(rng) & xorshift(rng);
+        if (mask.wrapping_mul(magic) >> 56).count_ones() < 6 {
+            continue;
+        }
+
+        attempt += 1;
+
+        let found = occupancies.iter().all(|&(occupied, attacks)| {
+            let idx = (occupied.wrapping_mul(magic) >> shift) as usize;
+            if epoch[idx] != attempt {
+                epoch[idx] = attempt;
+                used[idx] = attacks;
+                true
+            } else {
+                used[idx] == attacks
+            }
+        });
+
+        if found {
+            let offset = table.len();
+            table.extend_from_slice(&used);
+
+            return Magic {
+                mask,
+                magic,
+                shift,
+                offset,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sliding_attacks() {
+        let mut rng = 0x1234_5678_9abc_def0;
+
+        for _ in 0..1000 {
+            let occupied = xorshift(&mut rng) & xorshift(&mut rng);
+
+            for sq in 0..64 {
+                let coord = Coord::from_sq(sq);
+
+                assert_eq!(
+                    rook_attacks(coord, occupied),
+                    ray_attacks(sq, occupied, &ROOK_DIRECTIONS)
+                );
+                assert_eq!(
+                    bishop_attacks(coord, occupied),
+                    ray_attacks(sq, occupied, &BISHOP_DIRECTIONS)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_lines() {
+        let sq = |alg| Coord::from_alg(alg).unwrap();
+
+        assert_eq!(between(sq("a1"), sq("a4")), bit(sq("a2")) | bit(sq("a3")));
+        assert_eq!(between(sq("c1"), sq("f4")), bit(sq("d2")) | bit(sq("e3")));
+        assert_eq!(between(sq("c1"), sq("d3")), 0);
+        assert_eq!(between(sq("c1"), sq("d2")), 0);
+
+        assert_eq!(line(sq("b2"), sq("c3")).count_ones(), 8);
+        assert_eq!(line(sq("b2"), sq("b5")), 0x0202_0202_0202_0202);
+        assert_eq!(line(sq("b2"), sq("c4")), 0);
+    }
+
+    #[test]
+    fn test_leaper_attacks() {
+        assert_eq!(
+            Bits(KNIGHT_ATTACKS[0]).collect::<Vec<_>>(),
+            [
+                Coord::from_alg("c2").unwrap(),
+                Coord::from_alg("b3").unwrap()
+            ]
+        );
+
+        assert_eq!(
+            KING_ATTACKS[Coord::from_alg("e4").unwrap().to_sq()].count_ones(),
+            8
+        );
+
+        assert_eq!(
+            PAWN_ATTACKS[Color::Black as usize][Coord::from_alg("a7").unwrap().to_sq()],
+            bit(Coord::from_alg("b6").unwrap())
+        );
+    }
+}
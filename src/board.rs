@@ -1,118 +1,391 @@
-use crate::moves::{Move, gen_moves};
+use crate::moves::{Move, MoveFlag, MoveList, add_castling_moves, gen_moves};
 
 use super::*;
+use crate::bitboard::{
+    Bits, KING_ATTACKS, KNIGHT_ATTACKS, PAWN_ATTACKS, bishop_attacks, bit, queen_attacks,
+    rook_attacks,
+};
+use crate::zobrist::KEYS;
 
 use rapidhash::fast::{HashMapExt, RapidHashMap as HashMap};
 use std::error::Error;
 
 const START_POS: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 
-#[derive(Debug)]
-pub struct InvalidFenStringError {
-    fen: String,
+/// The fields of a FEN string, in order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FenField {
+    Placement,
+    SideToMove,
+    Castling,
+    EnPassant,
+    HalfmoveClock,
+    FullmoveNumber,
 }
 
-impl InvalidFenStringError {
-    fn new(fen: &str) -> Self {
-        Self {
-            fen: String::from(fen),
+impl Display for FenField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            FenField::Placement => "piece placement",
+            FenField::SideToMove => "side to move",
+            FenField::Castling => "castling rights",
+            FenField::EnPassant => "en passant square",
+            FenField::HalfmoveClock => "halfmove clock",
+            FenField::FullmoveNumber => "fullmove number",
+        })
+    }
+}
+
+/// Why a FEN string couldn't be read. [`FenError::field`] tells which field is at fault.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FenError {
+    /// One of the four fields that can't be left out is missing.
+    MissingField(FenField),
+    /// Something after the fullmove number.
+    TooManyFields,
+    /// The placement doesn't have 8 ranks.
+    WrongRankCount(usize),
+    /// The rank, numbered from 1, describes more than 8 squares.
+    TooManySquares(usize),
+    /// The rank, numbered from 1, describes fewer than 8 squares.
+    TooFewSquares(usize),
+    InvalidPiece(char),
+    InvalidSideToMove(String),
+    /// Not one of `KQkq`, or given twice.
+    InvalidCastling(char),
+    /// A castling right without the king and the rook on their starting squares.
+    CastlingWithoutPieces(char),
+    InvalidEnPassant(String),
+    /// An en passant square that no pawn could have just skipped over.
+    UnreachableEnPassant(String),
+    InvalidHalfmoveClock(String),
+    InvalidFullmoveNumber(String),
+}
+
+impl FenError {
+    /// The field the error is in.
+    pub fn field(&self) -> FenField {
+        match self {
+            FenError::MissingField(field) => *field,
+            FenError::TooManyFields => FenField::FullmoveNumber,
+            FenError::WrongRankCount(_)
+            | FenError::TooManySquares(_)
+            | FenError::TooFewSquares(_)
+            | FenError::InvalidPiece(_) => FenField::Placement,
+            FenError::InvalidSideToMove(_) => FenField::SideToMove,
+            FenError::InvalidCastling(_) | FenError::CastlingWithoutPieces(_) => FenField::Castling,
+            FenError::InvalidEnPassant(_) | FenError::UnreachableEnPassant(_) => {
+                FenField::EnPassant
+            }
+            FenError::InvalidHalfmoveClock(_) => FenField::HalfmoveClock,
+            FenError::InvalidFullmoveNumber(_) => FenField::FullmoveNumber,
+        }
+    }
+}
+
+impl Error for FenError {}
+
+impl Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid FEN {}: ", self.field())?;
+        match self {
+            FenError::MissingField(_) => write!(f, "missing"),
+            FenError::TooManyFields => write!(f, "followed by extra fields"),
+            FenError::WrongRankCount(n) => write!(f, "{n} ranks instead of 8"),
+            FenError::TooManySquares(rank) => write!(f, "more than 8 squares on rank {rank}"),
+            FenError::TooFewSquares(rank) => write!(f, "fewer than 8 squares on rank {rank}"),
+            FenError::InvalidPiece(c) => write!(f, "unknown piece {c}"),
+            FenError::InvalidSideToMove(txt) => write!(f, "{txt} is neither w nor b"),
+            FenError::InvalidCastling(c) => write!(f, "unexpected {c}"),
+            FenError::CastlingWithoutPieces(c) => {
+                write!(f, "{c} without the king and rook on their starting squares")
+            }
+            FenError::InvalidEnPassant(txt) => write!(f, "{txt} is not a square"),
+            FenError::UnreachableEnPassant(txt) => {
+                write!(f, "{txt} can't be skipped by the side that just moved")
+            }
+            FenError::InvalidHalfmoveClock(txt) | FenError::InvalidFullmoveNumber(txt) => {
+                write!(f, "{txt} is not a number")
+            }
         }
     }
 }
 
-impl Error for InvalidFenStringError {}
+/// Why a position can't occur in a game, as found by [`Board::validate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PositionError {
+    MissingKing(Color),
+    TooManyKings(Color),
+    PawnOnBackRank(Coord),
+    /// More pieces of some kind than promotions could account for.
+    TooManyPieces(Color),
+    /// The side that just moved left its king in check.
+    OpponentInCheck,
+    /// Checked by more pieces than a single move can produce.
+    TooManyCheckers,
+    /// The en passant square isn't right behind a pawn that could have just been pushed two
+    /// squares.
+    InvalidEnPassant(Coord),
+    /// A castling right, indexed like [`Board::castling`], without the king and rook on their
+    /// starting squares.
+    InvalidCastling(usize),
+    /// More halfmoves without a capture or pawn move than the seventy-five-move rule allows.
+    InvalidHalfmoveClock(u16),
+}
 
-impl Display for InvalidFenStringError {
+impl Display for PositionError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Invalid FEN String {}", self.fen)
+        write!(f, "Invalid position: ")?;
+        match self {
+            PositionError::MissingKing(color) => write!(f, "{color:?} has no king"),
+            PositionError::TooManyKings(color) => write!(f, "{color:?} has several kings"),
+            PositionError::PawnOnBackRank(coord) => write!(f, "pawn on {}", coord.to_alg()),
+            PositionError::TooManyPieces(color) => write!(f, "{color:?} has too many pieces"),
+            PositionError::OpponentInCheck => write!(f, "the side not to move is in check"),
+            PositionError::TooManyCheckers => write!(f, "too many pieces giving check"),
+            PositionError::InvalidEnPassant(coord) => {
+                write!(f, "no pawn to take en passant on {}", coord.to_alg())
+            }
+            PositionError::InvalidCastling(i) => write!(
+                f,
+                "castling right {} without the king and rook on their starting squares",
+                ['K', 'Q', 'k', 'q'][*i]
+            ),
+            PositionError::InvalidHalfmoveClock(n) => {
+                write!(f, "halfmove clock {n} is past the seventy-five-move rule")
+            }
+        }
     }
 }
 
+impl Error for PositionError {}
+
+/// The state [`Board::make_move`] can't recover from the move alone. Pass it back to
+/// [`Board::unmake_move`] to take the move back.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Undo {
+    /// The piece that was captured by the move, including a pawn taken en passant.
+    pub captured: Piece,
+    pub castling: [bool; 4],
+    pub en_pass_tgt: Option<Coord>,
+    pub halfmove_clock: u16,
+    /// Kept rather than counted back, since the number stops at `u16::MAX`.
+    pub fullmove_number: u16,
+    pub hash: u64,
+}
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct Board {
-    pub board: [Piece; 128],
+    board: [Piece; 128],
+    /// Bitboards of every piece, indexed by `Piece as usize`. Kept in sync with `board`.
+    pieces: [u64; 16],
+    /// Bitboards of all pieces of a color, indexed by `Color as usize`.
+    colors: [u64; 2],
     pub to_move: Color,
     pub castling: [bool; 4],
     pub en_pass_tgt: Option<Coord>,
+    /// Number of halfmoves since the last capture or pawn move, for the 50-move rule.
+    pub halfmove_clock: u16,
+    /// Starts at 1 and is incremented after every move by Black.
+    pub fullmove_number: u16,
+    hash: u64,
 }
 
 impl Board {
-    pub fn from_fen(fen: &str) -> Result<Self, InvalidFenStringError> {
-        let mut board = [Piece::Empty; 128];
-
-        let mut idx: usize = 112;
+    /// Reads a position from a FEN string. The move clocks may be left out, in which case they
+    /// are those of a fresh game.
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+        let mut fields = fen.split_ascii_whitespace();
+        let mut field = |field| fields.next().ok_or(FenError::MissingField(field));
 
-        let mut it = fen.split_ascii_whitespace();
+        let placement_field = field(FenField::Placement)?;
+        let to_move_field = field(FenField::SideToMove)?;
+        let castling_field = field(FenField::Castling)?;
+        let en_passant_field = field(FenField::EnPassant)?;
+        let halfmove_field = field(FenField::HalfmoveClock).ok();
+        let fullmove_field = field(FenField::FullmoveNumber).ok();
+        if field(FenField::FullmoveNumber).is_ok() {
+            return Err(FenError::TooManyFields);
+        }
 
-        for p in it.next().unwrap().chars() {
-            let piece = if let Ok(p) = Piece::from_char(p) {
-                p
-            } else {
-                match p {
-                    '0'..='8' => {
-                        idx += p.to_digit(10).unwrap() as usize;
-                        continue;
-                    }
+        // Indexed like the 0x88 board
+        let mut placement = [Piece::Empty; 128];
 
-                    '/' => {
-                        idx -= 24;
-                        continue;
-                    }
+        let ranks: Vec<_> = placement_field.split('/').collect();
+        if ranks.len() != 8 {
+            return Err(FenError::WrongRankCount(ranks.len()));
+        }
 
-                    ' ' => break,
+        for (rank, rank_field) in (0..8).rev().zip(ranks) {
+            let mut file = 0;
 
-                    _ => return Err(InvalidFenStringError::new(fen)),
+            for c in rank_field.chars() {
+                if let Some(n) = c.to_digit(10).filter(|n| (1..=8).contains(n)) {
+                    file += n as usize;
+                } else {
+                    let piece = Piece::from_char(c).map_err(|_| FenError::InvalidPiece(c))?;
+                    if file < 8 {
+                        placement[rank * 16 + file] = piece;
+                    }
+                    file += 1;
                 }
-            };
 
-            board[idx] = piece;
+                if file > 8 {
+                    return Err(FenError::TooManySquares(rank + 1));
+                }
+            }
 
-            if idx < 127 {
-                idx += 1;
-            } else {
-                break;
+            if file < 8 {
+                return Err(FenError::TooFewSquares(rank + 1));
             }
         }
 
-        let to_move = it.next().unwrap();
-
-        let to_move = if to_move == "w" {
-            Color::White
-        } else if to_move == "b" {
-            Color::Black
-        } else {
-            return Err(InvalidFenStringError::new(fen));
+        let to_move = match to_move_field {
+            "w" => Color::White,
+            "b" => Color::Black,
+            _ => return Err(FenError::InvalidSideToMove(to_move_field.to_string())),
         };
 
         let mut castling = [false; 4];
 
-        for c in it.next().unwrap().chars() {
-            match c {
-                '-' => break,
-                'K' => castling[0] = true,
-                'Q' => castling[1] = true,
-                'k' => castling[2] = true,
-                'q' => castling[3] = true,
+        if castling_field != "-" {
+            for c in castling_field.chars() {
+                let (i, color, rook_file) = match c {
+                    'K' => (0, Color::White, 7),
+                    'Q' => (1, Color::White, 0),
+                    'k' => (2, Color::Black, 7),
+                    'q' => (3, Color::Black, 0),
+                    _ => return Err(FenError::InvalidCastling(c)),
+                };
+                if castling[i] {
+                    return Err(FenError::InvalidCastling(c));
+                }
+
+                let home = if color == Color::White { 0 } else { 7 * 16 };
+                if placement[home + 4] != Piece::KingW.to_color(color)
+                    || placement[home + rook_file] != Piece::RookW.to_color(color)
+                {
+                    return Err(FenError::CastlingWithoutPieces(c));
+                }
 
-                _ => return Err(InvalidFenStringError::new(fen)),
+                castling[i] = true;
             }
         }
 
-        let en_pass_tgt = Coord::from_alg(it.next().unwrap()).ok();
+        let en_pass_tgt = match en_passant_field {
+            "-" => None,
+            _ => {
+                let tgt = Coord::from_alg(en_passant_field)
+                    .map_err(|_| FenError::InvalidEnPassant(en_passant_field.to_string()))?;
+
+                // The square behind a pawn that the other side just pushed two squares
+                let rank = if to_move == Color::White { 5 } else { 2 };
+                if tgt.rank() != rank {
+                    return Err(FenError::UnreachableEnPassant(en_passant_field.to_string()));
+                }
+
+                Some(tgt)
+            }
+        };
+
+        let halfmove_clock = match halfmove_field {
+            Some(n) => n
+                .parse()
+                .map_err(|_| FenError::InvalidHalfmoveClock(n.to_string()))?,
+            None => 0,
+        };
+
+        let fullmove_number = match fullmove_field {
+            Some(n) => n
+                .parse()
+                .map_err(|_| FenError::InvalidFullmoveNumber(n.to_string()))?,
+            None => 1,
+        };
 
-        Ok(Self {
-            board,
+        let mut board = Self {
+            board: [Piece::Empty; 128],
+            pieces: [0; 16],
+            colors: [0; 2],
             to_move,
             castling,
             en_pass_tgt,
-        })
+            halfmove_clock,
+            fullmove_number,
+            hash: 0,
+        };
+
+        for (idx, piece) in placement.into_iter().enumerate() {
+            if piece != Piece::Empty {
+                board.set_piece(Coord(idx as u8), piece);
+            }
+        }
+        board.hash = board.compute_hash();
+
+        Ok(board)
+    }
+
+    /// Serializes the position into a FEN string. Same as formatting the board with `{}`.
+    pub fn to_fen(&self) -> String {
+        self.to_string()
     }
 
     pub fn start_pos() -> Self {
         Self::from_fen(START_POS).unwrap()
     }
 
+    /// The bitboard of all pieces of the given kind and color.
+    pub fn pieces(&self, piece: Piece) -> u64 {
+        self.pieces[piece as usize]
+    }
+
+    /// The bitboard of all pieces of the given color.
+    pub fn color(&self, color: Color) -> u64 {
+        self.colors[color as usize]
+    }
+
+    /// The bitboard of all occupied squares.
+    pub fn occupied(&self) -> u64 {
+        self.colors[0] | self.colors[1]
+    }
+
+    /// The Zobrist key of the position. It is kept up to date incrementally by
+    /// [`Board::make_move`].
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Computes the Zobrist key of the position from scratch.
+    pub(crate) fn compute_hash(&self) -> u64 {
+        let mut hash = 0;
+
+        for (idx, &piece) in self.board.iter().enumerate() {
+            if piece != Piece::Empty {
+                hash ^= KEYS.pieces[piece as usize][idx];
+            }
+        }
+
+        if self.to_move == Color::Black {
+            hash ^= KEYS.black_to_move;
+        }
+
+        for (i, &c) in self.castling.iter().enumerate() {
+            if c {
+                hash ^= KEYS.castling[i];
+            }
+        }
+
+        if let Some(tgt) = self.en_pass_tgt {
+            hash ^= KEYS.en_passant[tgt.file() as usize];
+        }
+
+        hash
+    }
+
     pub fn perft(&self, depth: usize) -> u64 {
+        self.clone().perft_mut(depth)
+    }
+
+    fn perft_mut(&mut self, depth: usize) -> u64 {
         if depth == 0 {
             return 1;
         }
@@ -120,9 +393,9 @@ impl Board {
         let mut nodes = 0;
 
         for mov in gen_moves(self) {
-            let mut board = self.clone();
-            board.make_move(mov);
-            nodes += board.perft(depth - 1);
+            let undo = self.make_move(mov);
+            nodes += self.perft_mut(depth - 1);
+            self.unmake_move(mov, undo);
         }
 
         nodes
@@ -130,56 +403,98 @@ impl Board {
 
     pub fn perft_split(&self, depth: usize) -> HashMap<Move, u64> {
         let mut map = HashMap::new();
+        let mut board = self.clone();
 
         for mov in gen_moves(self) {
-            let mut board = self.clone();
-            board.make_move(mov);
-            let n = board.perft(depth - 1);
+            let undo = board.make_move(mov);
+            let n = board.perft_mut(depth - 1);
+            board.unmake_move(mov, undo);
             map.insert(mov, n);
         }
         map
     }
 
-    pub fn make_move(&mut self, mov: Move) {
-        let piece = self[mov.orig];
+    /// The square of the pawn captured by an en passant move landing on `dst`.
+    fn en_passant_victim(dst: Coord) -> Coord {
+        if dst.rank() == 5 {
+            dst.add((-1, 0)).unwrap()
+        } else {
+            dst.add((1, 0)).unwrap()
+        }
+    }
 
-        self.move_piece(mov);
+    /// The origin and destination of the rook when castling to the given king destination.
+    fn castling_rook(king_dst: Coord) -> (Coord, Coord) {
+        let r = king_dst.rank() as usize;
+        if king_dst.file() == 2 {
+            (Coord::from_rf(r, 0).unwrap(), Coord::from_rf(r, 3).unwrap())
+        } else {
+            (Coord::from_rf(r, 7).unwrap(), Coord::from_rf(r, 5).unwrap())
+        }
+    }
 
-        if let Some(tgt) = self.en_pass_tgt
-            && mov.dst == tgt
-        {
-            if piece == Piece::PawnW {
-                self[mov.dst.add((-1, 0)).unwrap()] = Piece::Empty;
-            } else if piece == Piece::PawnB {
-                self[mov.dst.add((1, 0)).unwrap()] = Piece::Empty;
-            }
+    /// Plays a move and returns what is needed to take it back with [`Board::unmake_move`].
+    pub fn make_move(&mut self, mov: Move) -> Undo {
+        let orig = mov.orig();
+        let dst = mov.dst();
+        let piece = self[orig];
+        let old_castling = self.castling;
+
+        let captured = if mov.is_en_passant() {
+            self[Self::en_passant_victim(dst)]
+        } else {
+            self[dst]
+        };
+
+        let undo = Undo {
+            captured,
+            castling: self.castling,
+            en_pass_tgt: self.en_pass_tgt,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            hash: self.hash,
+        };
+
+        if captured != Piece::Empty || piece.to_color(Color::White) == Piece::PawnW {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock = self.halfmove_clock.saturating_add(1);
         }
 
-        let (r, f) = mov.orig.to_rf();
+        if self.to_move == Color::Black {
+            self.fullmove_number = self.fullmove_number.saturating_add(1);
+        }
 
-        if piece.to_color(Color::White) == Piece::PawnW && r.abs_diff(mov.dst.rank() as usize) == 2
-        {
-            self.en_pass_tgt =
-                Some(Coord::from_rf(r.midpoint(mov.dst.rank() as usize), f).unwrap());
+        if let Some(tgt) = self.en_pass_tgt {
+            self.hash ^= KEYS.en_passant[tgt.file() as usize];
+        }
+
+        if mov.is_en_passant() {
+            self.set_piece(Self::en_passant_victim(dst), Piece::Empty);
+        }
+
+        let moved = mov
+            .promotion()
+            .map_or(piece, |p| p.to_color(piece.get_color()));
+        self.set_piece(dst, moved);
+        self.set_piece(orig, Piece::Empty);
+
+        let (r, f) = orig.to_rf();
+
+        if mov.is_double_push() {
+            self.en_pass_tgt = Some(Coord::from_rf(r.midpoint(dst.rank() as usize), f).unwrap());
         } else {
             self.en_pass_tgt = None;
         }
 
+        if let Some(tgt) = self.en_pass_tgt {
+            self.hash ^= KEYS.en_passant[tgt.file() as usize];
+        }
+
         // Move the rook when castling
-        if piece.to_color(Color::White) == Piece::KingW && f == 4 {
-            if mov.dst.file() == 2 {
-                self.move_piece(Move::new(
-                    (r, 0).try_into().unwrap(),
-                    (r, 3).try_into().unwrap(),
-                    None,
-                ));
-            } else if mov.dst.file() == 6 {
-                self.move_piece(Move::new(
-                    (r, 7).try_into().unwrap(),
-                    (r, 5).try_into().unwrap(),
-                    None,
-                ));
-            }
+        if mov.is_castle() {
+            let (rook_orig, rook_dst) = Self::castling_rook(dst);
+            self.move_piece(rook_orig, rook_dst);
         }
 
         // Update castling possibilty array
@@ -202,25 +517,419 @@ impl Board {
             }
         }
 
-        self.to_move = self.to_move.flip()
+        // Capturing a rook on its starting square takes that side's castling right with it
+        for (i, corner) in [0x07, 0x00, 0x77, 0x70].into_iter().enumerate() {
+            if dst == Coord(corner) {
+                self.castling[i] = false;
+            }
+        }
+
+        for (i, (new, old)) in self.castling.iter().zip(old_castling).enumerate() {
+            if *new != old {
+                self.hash ^= KEYS.castling[i];
+            }
+        }
+
+        self.to_move = self.to_move.flip();
+        self.hash ^= KEYS.black_to_move;
+
+        undo
     }
 
-    fn move_piece(&mut self, mov: Move) {
-        self[mov.dst] = mov.prom_tgt.map_or_else(
-            || self[mov.orig],
-            |p| p.to_color(self[mov.orig].get_color()),
-        );
-        self[mov.orig] = Piece::Empty;
+    /// Takes back a move played with [`Board::make_move`]. `undo` must be the value returned
+    /// when the move was made, and the move must be the last one played on the board.
+    pub fn unmake_move(&mut self, mov: Move, undo: Undo) {
+        let orig = mov.orig();
+        let dst = mov.dst();
+
+        self.to_move = self.to_move.flip();
+        self.fullmove_number = undo.fullmove_number;
+
+        let piece = if mov.is_promotion() {
+            Piece::PawnW.to_color(self.to_move)
+        } else {
+            self[dst]
+        };
+
+        self.set_piece(orig, piece);
+        self.set_piece(dst, Piece::Empty);
+
+        if mov.is_en_passant() {
+            self.set_piece(Self::en_passant_victim(dst), undo.captured);
+        } else {
+            self.set_piece(dst, undo.captured);
+        }
+
+        // Put the rook back when castling
+        if mov.is_castle() {
+            let (rook_orig, rook_dst) = Self::castling_rook(dst);
+            self.move_piece(rook_dst, rook_orig);
+        }
+
+        self.castling = undo.castling;
+        self.en_pass_tgt = undo.en_pass_tgt;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.hash = undo.hash;
+    }
+
+    fn move_piece(&mut self, orig: Coord, dst: Coord) {
+        self.set_piece(dst, self[orig]);
+        self.set_piece(orig, Piece::Empty);
     }
 
-    pub fn check_check(&self, moves: &[Move], color: Color) -> bool {
-        for mov in moves {
-            if self[mov.dst] == Piece::KingW.to_color(color.flip()) {
+    /// Puts a piece on a square, keeping the hash and bitboards up to date.
+    fn set_piece(&mut self, coord: Coord, piece: Piece) {
+        let old = self[coord];
+        if old != Piece::Empty {
+            self.hash ^= KEYS.pieces[old as usize][coord.0 as usize];
+            self.pieces[old as usize] ^= bit(coord);
+            self.colors[old.get_color() as usize] ^= bit(coord);
+        }
+        if piece != Piece::Empty {
+            self.hash ^= KEYS.pieces[piece as usize][coord.0 as usize];
+            self.pieces[piece as usize] ^= bit(coord);
+            self.colors[piece.get_color() as usize] ^= bit(coord);
+        }
+        self.board[coord.0 as usize] = piece;
+    }
+
+    /// The square of the king of the given color, if it has one.
+    pub fn king(&self, color: Color) -> Option<Coord> {
+        Bits(self.pieces(Piece::KingW.to_color(color))).next()
+    }
+
+    /// All pieces of color `by` attacking the square, with sliding pieces seeing through
+    /// everything not in `occupied`.
+    pub fn attackers(&self, coord: Coord, by: Color, occupied: u64) -> u64 {
+        let sq = coord.to_sq();
+
+        let queens = self.pieces(Piece::QueenW.to_color(by));
+        let rooks = self.pieces(Piece::RookW.to_color(by)) | queens;
+        let bishops = self.pieces(Piece::BishopW.to_color(by)) | queens;
+
+        // Look outward from the square: it is attacked by a piece exactly when that piece would
+        // attack the enemy piece if placed on the square
+        (PAWN_ATTACKS[by.flip() as usize][sq] & self.pieces(Piece::PawnW.to_color(by)))
+            | (KNIGHT_ATTACKS[sq] & self.pieces(Piece::KnightW.to_color(by)))
+            | (KING_ATTACKS[sq] & self.pieces(Piece::KingW.to_color(by)))
+            | (bishop_attacks(coord, occupied) & bishops)
+            | (rook_attacks(coord, occupied) & rooks)
+    }
+
+    /// Whether any piece of color `by` attacks the square. The square itself may be occupied by
+    /// anything.
+    pub fn is_square_attacked(&self, coord: Coord, by: Color) -> bool {
+        self.attackers(coord, by, self.occupied()) != 0
+    }
+
+    /// The squares attacked by the piece on the given square, regardless of what stands on them.
+    pub fn attacks_from(&self, coord: Coord) -> u64 {
+        let piece = self[coord];
+        let sq = coord.to_sq();
+
+        match piece.to_color(Color::White) {
+            Piece::PawnW => PAWN_ATTACKS[piece.get_color() as usize][sq],
+            Piece::KnightW => KNIGHT_ATTACKS[sq],
+            Piece::BishopW => bishop_attacks(coord, self.occupied()),
+            Piece::RookW => rook_attacks(coord, self.occupied()),
+            Piece::QueenW => queen_attacks(coord, self.occupied()),
+            Piece::KingW => KING_ATTACKS[sq],
+            _ => 0,
+        }
+    }
+
+    /// Whether the move could be played by the side to move, ignoring whether it leaves their
+    /// king in check. Castling is only accepted when the king doesn't castle out of, through or
+    /// into check. Cheaper than generating all moves, so it is meant for moves from outside the
+    /// generator: protocol input and moves remembered from other positions.
+    pub fn is_pseudo_legal(&self, mov: Move) -> bool {
+        let us = self.to_move;
+        let orig = mov.orig();
+        let dst = mov.dst();
+        let piece = self[orig];
+
+        // Also rejects the unused flag values, which could come from a corrupted hash entry
+        if mov == Move::NULL
+            || Move::new(orig, dst, mov.flag()) != mov
+            || piece == Piece::Empty
+            || piece.get_color() != us
+        {
+            return false;
+        }
+
+        if mov.is_castle() {
+            if piece.to_color(Color::White) != Piece::KingW {
+                return false;
+            }
+
+            let mut castles = MoveList::new();
+            add_castling_moves(self, orig, &mut castles);
+            return castles.contains(&mov);
+        }
+
+        let target = self[dst];
+        if target != Piece::Empty && (target.get_color() == us || mov.is_en_passant()) {
+            return false;
+        }
+        if mov.is_capture() != (target != Piece::Empty || mov.is_en_passant()) {
+            return false;
+        }
+
+        if piece.to_color(Color::White) != Piece::PawnW {
+            return !mov.is_promotion()
+                && !mov.is_en_passant()
+                && !mov.is_double_push()
+                && self.attacks_from(orig) & bit(dst) != 0;
+        }
+
+        let forward = if us == Color::White { 1 } else { -1 };
+        let last_rank = if us == Color::White { 7 } else { 0 };
+        if mov.is_promotion() != (dst.rank() == last_rank) {
+            return false;
+        }
+
+        match mov.flag() {
+            MoveFlag::EnPassant => {
+                self.en_pass_tgt == Some(dst)
+                    && PAWN_ATTACKS[us as usize][orig.to_sq()] & bit(dst) != 0
+            }
+            MoveFlag::DoublePush => {
+                let start_rank = if us == Color::White { 1 } else { 6 };
+                orig.rank() == start_rank
+                    && orig
+                        .add((forward, 0))
+                        .is_ok_and(|c| self[c] == Piece::Empty)
+                    && orig.add((forward * 2, 0)).ok() == Some(dst)
+            }
+            _ if mov.is_capture() => PAWN_ATTACKS[us as usize][orig.to_sq()] & bit(dst) != 0,
+            _ => orig.add((forward, 0)).ok() == Some(dst),
+        }
+    }
+
+    /// Whether the move is legal for the side to move, without generating all moves.
+    pub fn is_legal(&self, mov: Move) -> bool {
+        if !self.is_pseudo_legal(mov) {
+            return false;
+        }
+
+        let us = self.to_move;
+        let Some(king) = self.king(us) else {
+            return true;
+        };
+
+        let orig = mov.orig();
+        let dst = mov.dst();
+
+        // Castling already made sure the king stays out of check
+        if mov.is_castle() {
+            return true;
+        }
+
+        let captured = if mov.is_en_passant() {
+            bit(Self::en_passant_victim(dst))
+        } else {
+            bit(dst)
+        };
+        let occupied = ((self.occupied() ^ bit(orig)) & !captured) | bit(dst);
+        let king = if orig == king { dst } else { king };
+
+        self.attackers(king, us.flip(), occupied) & !captured == 0
+    }
+
+    /// Whether the move puts the opponent in check, either directly or by uncovering a slider.
+    pub fn gives_check(&self, mov: Move) -> bool {
+        let us = self.to_move;
+        let Some(king) = self.king(us.flip()) else {
+            return false;
+        };
+
+        let orig = mov.orig();
+        let dst = mov.dst();
+        let piece = mov
+            .promotion()
+            .unwrap_or_else(|| self[orig].to_color(Color::White));
+
+        let mut occupied = (self.occupied() ^ bit(orig)) | bit(dst);
+        let mut moved = bit(orig);
+
+        if mov.is_en_passant() {
+            occupied ^= bit(Self::en_passant_victim(dst));
+        }
+
+        // The rook is the only piece that can give check after castling
+        if mov.is_castle() {
+            let (rook_orig, rook_dst) = Self::castling_rook(dst);
+            occupied = (occupied ^ bit(rook_orig)) | bit(rook_dst);
+            moved |= bit(rook_orig);
+
+            if rook_attacks(rook_dst, occupied) & bit(king) != 0 {
                 return true;
             }
         }
 
-        false
+        let sq = dst.to_sq();
+        let direct = match piece {
+            Piece::PawnW => PAWN_ATTACKS[us as usize][sq],
+            Piece::KnightW => KNIGHT_ATTACKS[sq],
+            Piece::BishopW => bishop_attacks(dst, occupied),
+            Piece::RookW => rook_attacks(dst, occupied),
+            Piece::QueenW => queen_attacks(dst, occupied),
+            _ => 0,
+        };
+
+        if direct & bit(king) != 0 {
+            return true;
+        }
+
+        let queens = self.pieces(Piece::QueenW.to_color(us));
+        let rooks = self.pieces(Piece::RookW.to_color(us)) | queens;
+        let bishops = self.pieces(Piece::BishopW.to_color(us)) | queens;
+
+        let discovered =
+            (bishop_attacks(king, occupied) & bishops) | (rook_attacks(king, occupied) & rooks);
+
+        discovered & !moved != 0
+    }
+
+    /// Checks that the position could come up in a game. [`Board::from_fen`] only makes sure
+    /// the FEN is well formed, so positions from outside should go through this before being
+    /// searched.
+    pub fn validate(&self) -> Result<(), PositionError> {
+        for color in [Color::White, Color::Black] {
+            let count = |piece: Piece| self.pieces(piece.to_color(color)).count_ones();
+
+            match count(Piece::KingW) {
+                0 => return Err(PositionError::MissingKing(color)),
+                1 => {}
+                _ => return Err(PositionError::TooManyKings(color)),
+            }
+
+            // Every piece beyond the starting set has to be a promoted pawn
+            let promoted = count(Piece::QueenW).saturating_sub(1)
+                + count(Piece::RookW).saturating_sub(2)
+                + count(Piece::BishopW).saturating_sub(2)
+                + count(Piece::KnightW).saturating_sub(2);
+            if count(Piece::PawnW) + promoted > 8 {
+                return Err(PositionError::TooManyPieces(color));
+            }
+        }
+
+        let pawns = self.pieces(Piece::PawnW) | self.pieces(Piece::PawnB);
+        if let Some(coord) = Bits(pawns & 0xff00_0000_0000_00ff).next() {
+            return Err(PositionError::PawnOnBackRank(coord));
+        }
+
+        let them = self.to_move.flip();
+        let their_king = self.king(them).unwrap();
+        if self.is_square_attacked(their_king, self.to_move) {
+            return Err(PositionError::OpponentInCheck);
+        }
+
+        let our_king = self.king(self.to_move).unwrap();
+        if self.attackers(our_king, them, self.occupied()).count_ones() > 2 {
+            return Err(PositionError::TooManyCheckers);
+        }
+
+        if let Some(tgt) = self.en_pass_tgt {
+            // The pushed pawn is in front of the square, and the square it came from is empty
+            let forward = if them == Color::White { 1 } else { -1 };
+            let rank = if them == Color::White { 2 } else { 5 };
+
+            let pushed = tgt.rank() == rank
+                && self[tgt] == Piece::Empty
+                && tgt
+                    .add((forward, 0))
+                    .is_ok_and(|c| self[c] == Piece::PawnW.to_color(them))
+                && tgt
+                    .add((-forward, 0))
+                    .is_ok_and(|c| self[c] == Piece::Empty);
+            if !pushed {
+                return Err(PositionError::InvalidEnPassant(tgt));
+            }
+        }
+
+        for (i, &allowed) in self.castling.iter().enumerate() {
+            let color = if i < 2 { Color::White } else { Color::Black };
+            let rank = if color == Color::White { 0 } else { 7 };
+            let rook_file = if i % 2 == 0 { 7 } else { 0 };
+
+            if allowed
+                && (self[Coord::from_rf(rank, 4).unwrap()] != Piece::KingW.to_color(color)
+                    || self[Coord::from_rf(rank, rook_file).unwrap()]
+                        != Piece::RookW.to_color(color))
+            {
+                return Err(PositionError::InvalidCastling(i));
+            }
+        }
+
+        // The game ends automatically once the clock reaches 150
+        if self.halfmove_clock > 150 {
+            return Err(PositionError::InvalidHalfmoveClock(self.halfmove_clock));
+        }
+
+        Ok(())
+    }
+
+    /// Whether the side to move is in check.
+    pub fn in_check(&self) -> bool {
+        self.king(self.to_move)
+            .is_some_and(|king| self.is_square_attacked(king, self.to_move.flip()))
+    }
+}
+
+impl Display for Board {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+
+            for file in 0..8 {
+                let piece = self[Coord::from_rf(rank, file).unwrap()];
+
+                if piece == Piece::Empty {
+                    empty += 1;
+                    continue;
+                }
+
+                if empty != 0 {
+                    write!(f, "{empty}")?;
+                    empty = 0;
+                }
+                write!(f, "{}", char::from(piece))?;
+            }
+
+            if empty != 0 {
+                write!(f, "{empty}")?;
+            }
+            if rank != 0 {
+                write!(f, "/")?;
+            }
+        }
+
+        let to_move = match self.to_move {
+            Color::White => 'w',
+            Color::Black => 'b',
+        };
+        write!(f, " {to_move} ")?;
+
+        if self.castling == [false; 4] {
+            write!(f, "-")?;
+        } else {
+            for (c, allowed) in ['K', 'Q', 'k', 'q'].into_iter().zip(self.castling) {
+                if allowed {
+                    write!(f, "{c}")?;
+                }
+            }
+        }
+
+        let en_pass_tgt = self
+            .en_pass_tgt
+            .map_or_else(|| String::from("-"), Coord::to_alg);
+
+        write!(
+            f,
+            " {} {} {}",
+            en_pass_tgt, self.halfmove_clock, self.fullmove_number
+        )
     }
 }
 
@@ -231,15 +940,10 @@ impl std::ops::Index<Coord> for Board {
     }
 }
 
-impl std::ops::IndexMut<Coord> for Board {
-    fn index_mut(&mut self, index: Coord) -> &mut Self::Output {
-        &mut self.board[index.0 as usize]
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::moves::{MoveFlag, gen_moves_illegal};
 
     #[test]
     fn test_start_pos() {
@@ -384,6 +1088,10 @@ mod tests {
         assert_eq!(board.castling, [true, true, true, true]);
 
         assert_eq!(board.en_pass_tgt, None);
+
+        assert_eq!(board.halfmove_clock, 0);
+
+        assert_eq!(board.fullmove_number, 1);
     }
 
     #[test]
@@ -427,13 +1135,99 @@ mod tests {
     #[test]
     fn test_en_passant() {
         assert_eq!(
-            Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - e3 0 1")
+            Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b - e3 0 1")
                 .unwrap()
                 .en_pass_tgt,
             Some(Coord(0x24))
         );
     }
 
+    #[test]
+    fn test_move_clocks() {
+        let board =
+            Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 5 12").unwrap();
+        assert_eq!(board.halfmove_clock, 5);
+        assert_eq!(board.fullmove_number, 12);
+
+        let board =
+            Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -").unwrap();
+        assert_eq!(board.halfmove_clock, 0);
+        assert_eq!(board.fullmove_number, 1);
+
+        assert!(
+            Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - x 1").is_err()
+        );
+    }
+
+    #[test]
+    fn test_move_clock_updates() {
+        let mut board = Board::from_fen("4k3/8/8/3p4/8/8/8/4K1N1 w - - 3 10").unwrap();
+
+        // Quiet piece move by White
+        board.make_move(Move::new(Coord(0x06), Coord(0x14), MoveFlag::Quiet));
+        assert_eq!(board.halfmove_clock, 4);
+        assert_eq!(board.fullmove_number, 10);
+
+        // Pawn move by Black
+        board.make_move(Move::new(Coord(0x43), Coord(0x33), MoveFlag::Quiet));
+        assert_eq!(board.halfmove_clock, 0);
+        assert_eq!(board.fullmove_number, 11);
+
+        // Quiet piece move by White
+        board.make_move(Move::new(Coord(0x14), Coord(0x22), MoveFlag::Quiet));
+        assert_eq!(board.halfmove_clock, 1);
+        assert_eq!(board.fullmove_number, 11);
+
+        // Capture by Black
+        board.make_move(Move::new(Coord(0x33), Coord(0x22), MoveFlag::Capture));
+        assert_eq!(board.halfmove_clock, 0);
+        assert_eq!(board.fullmove_number, 12);
+
+        // The clocks stop at their limit instead of overflowing
+        let fen = "4k3/8/8/8/8/8/8/4K2R b - - 65535 65535";
+        let mut board = Board::from_fen(fen).unwrap();
+        let mov = Move::new(Coord(0x74), Coord(0x73), MoveFlag::Quiet);
+        let undo = board.make_move(mov);
+        assert_eq!(board.halfmove_clock, u16::MAX);
+        assert_eq!(board.fullmove_number, u16::MAX);
+        board.unmake_move(mov, undo);
+        assert_eq!(board.to_fen(), fen);
+    }
+
+    #[test]
+    fn test_to_fen() {
+        assert_eq!(Board::start_pos().to_fen(), START_POS);
+
+        let fen = "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2";
+        assert_eq!(Board::from_fen(fen).unwrap().to_string(), fen);
+
+        let fen = "8/8/8/8/8/8/8/K6k b - - 42 100";
+        assert_eq!(Board::from_fen(fen).unwrap().to_string(), fen);
+    }
+
+    #[test]
+    fn test_attacks() {
+        let board = Board::from_fen("4k3/8/8/3q4/8/1N6/4P3/R3K3 w - - 0 1").unwrap();
+        let sq = |alg| Coord::from_alg(alg).unwrap();
+
+        assert!(board.is_square_attacked(sq("d2"), Color::White));
+        assert!(board.is_square_attacked(sq("f3"), Color::White));
+        assert!(board.is_square_attacked(sq("a8"), Color::White));
+        assert!(board.is_square_attacked(sq("c5"), Color::White));
+        assert!(!board.is_square_attacked(sq("e3"), Color::White));
+
+        assert!(board.is_square_attacked(sq("a5"), Color::Black));
+        assert!(board.is_square_attacked(sq("h1"), Color::Black));
+        assert!(!board.is_square_attacked(sq("b1"), Color::Black));
+        assert!(!board.in_check());
+
+        let board = Board::from_fen("4k3/8/8/8/1b6/8/8/4K3 w - - 0 1").unwrap();
+        assert!(board.in_check());
+
+        let board = Board::from_fen("4k3/8/8/8/1b6/2P5/8/4K3 w - - 0 1").unwrap();
+        assert!(!board.in_check());
+    }
+
     #[test]
     fn test_turn() {
         assert_eq!(
@@ -443,4 +1237,265 @@ mod tests {
             Color::Black
         );
     }
+
+    #[test]
+    fn test_legality() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "8/8/8/2k5/3Pp3/8/8/4K2Q b - d3 0 1",
+            "4k3/8/8/K2pP2r/8/8/8/8 w - d6 0 1",
+        ];
+
+        let check = |board: &Board| {
+            let legal = gen_moves(board);
+            let pseudo_legal = gen_moves_illegal(board);
+
+            // Every possible encoding, including nonsense like moves from empty squares
+            for raw in 0..=u16::MAX {
+                let mov = Move::from_u16(raw);
+                assert_eq!(board.is_legal(mov), legal.contains(&mov), "{board} {mov:?}");
+                assert_eq!(
+                    board.is_pseudo_legal(mov),
+                    pseudo_legal.contains(&mov),
+                    "{board} {mov:?}"
+                );
+            }
+        };
+
+        for fen in fens {
+            let board = Board::from_fen(fen).unwrap();
+            check(&board);
+
+            for mov in gen_moves(&board) {
+                let mut board = board.clone();
+                board.make_move(mov);
+                check(&board);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fen_errors() {
+        let error = |fen| Board::from_fen(fen).unwrap_err();
+
+        assert_eq!(error(""), FenError::MissingField(FenField::Placement));
+        assert_eq!(
+            error("8/8/8/8/8/8/8/8 w -"),
+            FenError::MissingField(FenField::EnPassant)
+        );
+        assert_eq!(
+            error("8/8/8/8/8/8/8/8 w - - 0 1 x"),
+            FenError::TooManyFields
+        );
+
+        assert_eq!(error("8/8/8/8/8/8/8 w - -"), FenError::WrongRankCount(7));
+        assert_eq!(error("8/8/8/8/8/8/8/8/ w - -"), FenError::WrongRankCount(9));
+        assert_eq!(
+            error("8/8/8/8/8/8/8/ppppppppp w - -"),
+            FenError::TooManySquares(1)
+        );
+        assert_eq!(
+            error("8/8/8/8/8/8/8/7p1 w - -"),
+            FenError::TooManySquares(1)
+        );
+        assert_eq!(error("8/9/8/8/8/8/8/8 w - -"), FenError::InvalidPiece('9'));
+        assert_eq!(error("8/0/8/8/8/8/8/8 w - -"), FenError::InvalidPiece('0'));
+        assert_eq!(
+            error("8/8/8/8/8/8/8/44x w - -"),
+            FenError::InvalidPiece('x')
+        );
+        assert_eq!(error("8/8/8/8/8/8/8/43 w - -"), FenError::TooFewSquares(1));
+        assert_eq!(error("8/8//8/8/8/8/8 w - -"), FenError::TooFewSquares(6));
+
+        assert_eq!(
+            error("8/8/8/8/8/8/8/8 W - -"),
+            FenError::InvalidSideToMove("W".to_string())
+        );
+
+        assert_eq!(
+            error("8/8/8/8/8/8/8/8 w x -"),
+            FenError::InvalidCastling('x')
+        );
+        assert_eq!(
+            error("r3k2r/8/8/8/8/8/8/R3K2R w KK -"),
+            FenError::InvalidCastling('K')
+        );
+        assert_eq!(
+            error("r3k2r/8/8/8/8/8/8/R3K1R1 w KQkq -"),
+            FenError::CastlingWithoutPieces('K')
+        );
+        assert_eq!(
+            error("r3k2r/8/8/8/8/8/8/R2K3R w Q -"),
+            FenError::CastlingWithoutPieces('Q')
+        );
+
+        assert_eq!(
+            error("8/8/8/8/8/8/8/8 w - e9"),
+            FenError::InvalidEnPassant("e9".to_string())
+        );
+        assert_eq!(
+            error("8/8/8/8/8/8/8/8 w - e3"),
+            FenError::UnreachableEnPassant("e3".to_string())
+        );
+
+        assert_eq!(
+            error("8/8/8/8/8/8/8/8 w - - -1"),
+            FenError::InvalidHalfmoveClock("-1".to_string())
+        );
+        assert_eq!(
+            error("8/8/8/8/8/8/8/8 w - - 0 x"),
+            FenError::InvalidFullmoveNumber("x".to_string())
+        );
+
+        assert_eq!(error("8/8/8/8/8/8/8 w - -").field(), FenField::Placement);
+        assert_eq!(
+            error("8/8/8/8/8/8/8/8 w - e3").to_string(),
+            "Invalid FEN en passant square: e3 can't be skipped by the side that just moved"
+        );
+    }
+
+    /// Throws mangled FEN strings at the parser. It must never panic, and whatever it accepts has
+    /// to survive a round trip. Boards that also pass [`Board::validate`] have to survive a move
+    /// being made and unmade.
+    #[test]
+    fn test_fen_fuzz() {
+        let fens = [
+            START_POS,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - -",
+            // The largest clocks that still pass validation
+            "4k3/8/8/8/8/8/8/4K2R b - - 150 65535",
+        ];
+        assert!(Board::from_fen(fens[4]).unwrap().validate().is_ok());
+        let alphabet: Vec<char> = "pnbrqkPNBRQK0123456789/ -wbKQkqabcdefgh\u{e9}x"
+            .chars()
+            .collect();
+
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut random = move |n: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize % n
+        };
+
+        for i in 0..50_000 {
+            let mut fen: Vec<char> = fens[i % fens.len()].chars().collect();
+
+            for _ in 0..=random(4) {
+                let c = alphabet[random(alphabet.len())];
+                let idx = random(fen.len() + 1);
+                match random(3) {
+                    0 => fen.insert(idx, c),
+                    1 if idx < fen.len() => fen[idx] = c,
+                    _ if idx < fen.len() => {
+                        fen.remove(idx);
+                    }
+                    _ => {}
+                }
+            }
+
+            let fen: String = fen.into_iter().collect();
+            if let Ok(mut board) = Board::from_fen(&fen) {
+                assert_eq!(Board::from_fen(&board.to_fen()).unwrap(), board, "{fen}");
+
+                // Only sane positions can be played on from
+                if board.validate().is_ok()
+                    && let Some(&mov) = gen_moves(&board).first()
+                {
+                    let before = board.clone();
+                    let undo = board.make_move(mov);
+                    board.unmake_move(mov, undo);
+                    assert_eq!(board, before, "{fen} {mov}");
+                }
+            }
+        }
+
+        // Random garbage, mostly failing early
+        for _ in 0..50_000 {
+            let len = random(80);
+            let fen: String = (0..len).map(|_| alphabet[random(alphabet.len())]).collect();
+            let _ = Board::from_fen(&fen);
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let error = |fen| Board::from_fen(fen).unwrap().validate().unwrap_err();
+
+        assert_eq!(Board::start_pos().validate(), Ok(()));
+        assert_eq!(
+            Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1")
+                .unwrap()
+                .validate(),
+            Ok(())
+        );
+        assert_eq!(
+            Board::from_fen("4k3/8/8/8/8/8/8/QQQQKQQQ w - - 0 1")
+                .unwrap()
+                .validate(),
+            Ok(())
+        );
+
+        assert_eq!(
+            error("8/8/8/8/8/8/8/4K3 w - - 0 1"),
+            PositionError::MissingKing(Color::Black)
+        );
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/3KK3 w - - 0 1"),
+            PositionError::TooManyKings(Color::White)
+        );
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/4K2p w - - 0 1"),
+            PositionError::PawnOnBackRank(Coord::from_alg("h1").unwrap())
+        );
+        assert_eq!(
+            error("4k3/8/8/8/8/8/PPP5/QQQQKQQQ w - - 0 1"),
+            PositionError::TooManyPieces(Color::White)
+        );
+        assert_eq!(
+            error("3nknn1/pppppppp/8/8/8/8/8/4K3 w - - 0 1"),
+            PositionError::TooManyPieces(Color::Black)
+        );
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1"),
+            PositionError::OpponentInCheck
+        );
+        assert_eq!(
+            error("4k3/8/8/8/1b6/3n4/8/r3K3 w - - 0 1"),
+            PositionError::TooManyCheckers
+        );
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/4K3 b - e3 0 1"),
+            PositionError::InvalidEnPassant(Coord::from_alg("e3").unwrap())
+        );
+        assert_eq!(
+            error("4k3/8/8/8/4P3/8/4P3/4K3 b - e3 0 1"),
+            PositionError::InvalidEnPassant(Coord::from_alg("e3").unwrap())
+        );
+
+        let mut board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        board.make_move(Move::from_uci("a1a2", &board).unwrap());
+        board.castling[1] = true;
+        assert_eq!(board.validate(), Err(PositionError::InvalidCastling(1)));
+
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/4K2R b - - 151 100"),
+            PositionError::InvalidHalfmoveClock(151)
+        );
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/4K2R b - - 65535 65535"),
+            PositionError::InvalidHalfmoveClock(u16::MAX)
+        );
+        assert!(
+            Board::from_fen("4k3/8/8/8/8/8/8/4K2R b - - 150 100")
+                .unwrap()
+                .validate()
+                .is_ok()
+        );
+    }
 }
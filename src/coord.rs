@@ -67,15 +67,23 @@ impl Coord {
         self.0 & 7
     }
 
+    /// Converts a square index (`rank * 8 + file`, as used by bitboards) to a coordinate.
+    pub const fn from_sq(sq: usize) -> Self {
+        Self((((sq / 8) << 4) | (sq % 8)) as u8)
+    }
+
+    /// Converts the coordinate to a square index (`rank * 8 + file`, as used by bitboards).
+    pub const fn to_sq(self) -> usize {
+        ((self.0 >> 4) * 8 + (self.0 & 7)) as usize
+    }
+
     pub fn from_alg(alg: &str) -> Result<Self, InvalidCoordinateError> {
-        if alg.len() != 2 {
-            return Err(InvalidCoordinateError::new_alg(alg));
+        match alg.as_bytes() {
+            &[file @ b'a'..=b'h', rank @ b'1'..=b'8'] => {
+                Self::from_rf((rank - b'1') as usize, (file - b'a') as usize)
+            }
+            _ => Err(InvalidCoordinateError::new_alg(alg)),
         }
-        let mut chars = alg.chars();
-        let file = (chars.next().unwrap() as usize) - ('a' as usize);
-        let rank = (chars.next().unwrap() as usize) - ('1' as usize);
-
-        Self::from_rf(rank, file)
     }
 
     pub fn to_alg(self) -> String {
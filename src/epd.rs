@@ -0,0 +1,467 @@
+//! Extended Position Description, the format test suites like WAC and STS come in: the first
+//! four FEN fields followed by operations like `bm Qg6; id "WAC.001";`.
+
+use super::*;
+use crate::moves::Move;
+use crate::san::SanParseError;
+use std::str::FromStr;
+
+/// A single EPD operation. The opcodes without a variant of their own are kept as
+/// [`Operation::Other`], with their operands as written.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Operation {
+    /// `bm`: the best moves in the position.
+    BestMoves(Vec<Move>),
+    /// `am`: moves to avoid.
+    AvoidMoves(Vec<Move>),
+    /// `id`: the name of the position within its suite.
+    Id(String),
+    /// `c0` to `c9`: comments, with the digit of the opcode.
+    Comment(u8, String),
+    /// `ce`: the evaluation in centipawns, from the point of view of the side to move.
+    CentipawnEval(i32),
+    /// `dm`: the side to move mates in this many moves.
+    DirectMate(u32),
+    Other {
+        opcode: String,
+        operands: Vec<String>,
+    },
+}
+
+impl Operation {
+    pub fn opcode(&self) -> String {
+        match self {
+            Operation::BestMoves(_) => "bm".to_string(),
+            Operation::AvoidMoves(_) => "am".to_string(),
+            Operation::Id(_) => "id".to_string(),
+            Operation::Comment(n, _) => format!("c{n}"),
+            Operation::CentipawnEval(_) => "ce".to_string(),
+            Operation::DirectMate(_) => "dm".to_string(),
+            Operation::Other { opcode, .. } => opcode.clone(),
+        }
+    }
+}
+
+/// A position along with its operations. The move clocks, which EPD has no fields for, are
+/// taken from the `hmvc` and `fmvn` operations when they are given.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Epd {
+    pub board: Board,
+    /// In the order they were read.
+    pub operations: Vec<Operation>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EpdError {
+    /// Fewer than the four position fields.
+    MissingField,
+    InvalidPosition(String),
+    InvalidMove {
+        opcode: String,
+        err: SanParseError,
+    },
+    InvalidOperand {
+        opcode: String,
+        operand: String,
+    },
+    /// An operation that needs an operand but has none, like `id;`.
+    MissingOperand(String),
+    UnterminatedString,
+}
+
+impl Display for EpdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EpdError::MissingField => write!(f, "Invalid EPD: missing position fields"),
+            EpdError::InvalidPosition(fen) => write!(f, "Invalid EPD position: {fen}"),
+            EpdError::InvalidMove { opcode, err } => write!(f, "Invalid EPD {opcode}: {err}"),
+            EpdError::InvalidOperand { opcode, operand } => {
+                write!(f, "Invalid EPD {opcode} operand: {operand}")
+            }
+            EpdError::MissingOperand(opcode) => write!(f, "Missing EPD {opcode} operand"),
+            EpdError::UnterminatedString => write!(f, "Invalid EPD: unterminated string"),
+        }
+    }
+}
+
+impl Error for EpdError {}
+
+impl Epd {
+    pub fn new(board: Board) -> Self {
+        Self {
+            board,
+            operations: Vec::new(),
+        }
+    }
+
+    /// The operation with the given opcode.
+    pub fn get(&self, opcode: &str) -> Option<&Operation> {
+        self.operations.iter().find(|op| op.opcode() == opcode)
+    }
+
+    /// Adds an operation, replacing the one with the same opcode if there is one.
+    pub fn set(&mut self, operation: Operation) {
+        let opcode = operation.opcode();
+        match self.operations.iter_mut().find(|op| op.opcode() == opcode) {
+            Some(op) => *op = operation,
+            None => self.operations.push(operation),
+        }
+    }
+
+    pub fn best_moves(&self) -> &[Move] {
+        match self.get("bm") {
+            Some(Operation::BestMoves(moves)) => moves,
+            _ => &[],
+        }
+    }
+
+    pub fn avoid_moves(&self) -> &[Move] {
+        match self.get("am") {
+            Some(Operation::AvoidMoves(moves)) => moves,
+            _ => &[],
+        }
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        match self.get("id") {
+            Some(Operation::Id(id)) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// The comment of the `c0` to `c9` operation with the given digit.
+    pub fn comment(&self, n: u8) -> Option<&str> {
+        self.operations.iter().find_map(|op| match op {
+            Operation::Comment(i, comment) if *i == n => Some(comment.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn centipawn_eval(&self) -> Option<i32> {
+        match self.get("ce") {
+            Some(&Operation::CentipawnEval(eval)) => Some(eval),
+            _ => None,
+        }
+    }
+
+    pub fn direct_mate(&self) -> Option<u32> {
+        match self.get("dm") {
+            Some(&Operation::DirectMate(moves)) => Some(moves),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Epd {
+    type Err = EpdError;
+
+    fn from_str(epd: &str) -> Result<Self, Self::Err> {
+        let epd = epd.trim();
+
+        // The four position fields, then everything else is operations
+        let mut fields = Vec::new();
+        let mut rest = epd;
+        for _ in 0..4 {
+            let (field, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            if field.is_empty() {
+                return Err(EpdError::MissingField);
+            }
+            fields.push(field);
+            rest = tail.trim_start();
+        }
+
+        let fen = fields.join(" ");
+        let mut board =
+            Board::from_fen(&fen).map_err(|_| EpdError::InvalidPosition(fen.clone()))?;
+
+        let mut operations = Vec::new();
+
+        for words in split_operations(rest)? {
+            let Some((opcode, operands)) = words.split_first() else {
+                continue;
+            };
+            let opcode = opcode.clone();
+
+            let moves = |board: &Board| {
+                operands
+                    .iter()
+                    .map(|san| Move::from_san(san, board))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|err| EpdError::InvalidMove {
+                        opcode: opcode.clone(),
+                        err,
+                    })
+            };
+            let comment_digit = opcode
+                .strip_prefix('c')
+                .and_then(|n| n.parse::<u8>().ok())
+                .filter(|_| opcode.len() == 2);
+
+            let operation = match opcode.as_str() {
+                "bm" => Operation::BestMoves(moves(&board)?),
+                "am" => Operation::AvoidMoves(moves(&board)?),
+                "id" => Operation::Id(operand(&opcode, operands)?.clone()),
+                "ce" => Operation::CentipawnEval(number(&opcode, operands)?),
+                "dm" => Operation::DirectMate(number(&opcode, operands)?),
+
+                // The move clocks belong to the position
+                "hmvc" => {
+                    board.halfmove_clock = number(&opcode, operands)?;
+                    continue;
+                }
+                "fmvn" => {
+                    board.fullmove_number = number(&opcode, operands)?;
+                    continue;
+                }
+
+                _ => match comment_digit {
+                    Some(n) => Operation::Comment(n, operand(&opcode, operands)?.clone()),
+                    None => Operation::Other {
+                        opcode: opcode.clone(),
+                        operands: operands.to_vec(),
+                    },
+                },
+            };
+            operations.push(operation);
+        }
+
+        Ok(Self { board, operations })
+    }
+}
+
+/// The first operand of an operation.
+fn operand<'a>(opcode: &str, operands: &'a [String]) -> Result<&'a String, EpdError> {
+    operands
+        .first()
+        .ok_or_else(|| EpdError::MissingOperand(opcode.to_string()))
+}
+
+/// The first operand of an operation, as a number.
+fn number<T: FromStr>(opcode: &str, operands: &[String]) -> Result<T, EpdError> {
+    let operand = operand(opcode, operands)?;
+    operand.parse().map_err(|_| EpdError::InvalidOperand {
+        opcode: opcode.to_string(),
+        operand: operand.clone(),
+    })
+}
+
+/// Splits the operations into their words: the opcode followed by the operands. Operands in
+/// quotes can contain spaces and semicolons.
+fn split_operations(txt: &str) -> Result<Vec<Vec<String>>, EpdError> {
+    let mut operations = Vec::new();
+    let mut words = Vec::new();
+    let mut word = String::new();
+
+    let mut chars = txt.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '"' {
+                        closed = true;
+                        break;
+                    }
+                    word.push(c);
+                }
+                if !closed {
+                    return Err(EpdError::UnterminatedString);
+                }
+                words.push(std::mem::take(&mut word));
+            }
+            ';' | ' ' | '\t' => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+                if c == ';' {
+                    operations.push(std::mem::take(&mut words));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+
+    if !word.is_empty() {
+        words.push(word);
+    }
+    if !words.is_empty() {
+        operations.push(words);
+    }
+
+    Ok(operations)
+}
+
+impl Display for Epd {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fen = self.board.to_fen();
+        let fields: Vec<_> = fen.split(' ').take(4).collect();
+        write!(f, "{}", fields.join(" "))?;
+
+        let quote = |txt: &str| format!("\"{}\"", txt.replace('"', ""));
+        let moves = |moves: &[Move]| {
+            moves
+                .iter()
+                .map(|mov| mov.to_san(&self.board))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        for operation in &self.operations {
+            let operands = match operation {
+                Operation::BestMoves(m) | Operation::AvoidMoves(m) => moves(m),
+                Operation::Id(txt) | Operation::Comment(_, txt) => quote(txt),
+                Operation::CentipawnEval(eval) => eval.to_string(),
+                Operation::DirectMate(moves) => moves.to_string(),
+                Operation::Other { operands, .. } => operands
+                    .iter()
+                    .map(|operand| {
+                        if operand.is_empty() || operand.contains([' ', '\t', ';']) {
+                            quote(operand)
+                        } else {
+                            operand.clone()
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            };
+
+            if operands.is_empty() {
+                write!(f, " {};", operation.opcode())?;
+            } else {
+                write!(f, " {} {operands};", operation.opcode())?;
+            }
+        }
+
+        // EPD has no fields for the clocks, so only write them when they carry information
+        if self.board.halfmove_clock != 0 {
+            write!(f, " hmvc {};", self.board.halfmove_clock)?;
+        }
+        if self.board.fullmove_number != 1 {
+            write!(f, " fmvn {};", self.board.fullmove_number)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Epd, EpdError, Operation};
+    use crate::moves::Move;
+    use crate::san::SanParseError;
+
+    #[test]
+    fn test_read_epd() {
+        let epd: Epd =
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";"
+                .parse()
+                .unwrap();
+
+        assert_eq!(
+            epd.board.to_fen(),
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1"
+        );
+        assert_eq!(
+            epd.best_moves(),
+            [Move::from_uci("g3g6", &epd.board).unwrap()]
+        );
+        assert_eq!(epd.avoid_moves(), []);
+        assert_eq!(epd.id(), Some("WAC.001"));
+        assert_eq!(epd.centipawn_eval(), None);
+
+        let epd: Epd = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - am O-O Rxa8+; c0 \"Don't; castle\"; \
+                        c9 \"x\"; ce -35; dm 2; hmvc 7; fmvn 31; acn 1000; pm; sv e4 \"a b\";"
+            .parse()
+            .unwrap();
+
+        assert_eq!(epd.board.halfmove_clock, 7);
+        assert_eq!(epd.board.fullmove_number, 31);
+        assert_eq!(epd.avoid_moves().len(), 2);
+        assert_eq!(epd.comment(0), Some("Don't; castle"));
+        assert_eq!(epd.comment(9), Some("x"));
+        assert_eq!(epd.comment(1), None);
+        assert_eq!(epd.centipawn_eval(), Some(-35));
+        assert_eq!(epd.direct_mate(), Some(2));
+        assert_eq!(
+            epd.get("sv"),
+            Some(&Operation::Other {
+                opcode: "sv".to_string(),
+                operands: vec!["e4".to_string(), "a b".to_string()],
+            })
+        );
+        assert_eq!(
+            epd.get("pm"),
+            Some(&Operation::Other {
+                opcode: "pm".to_string(),
+                operands: Vec::new(),
+            })
+        );
+
+        // No operations at all
+        let epd: Epd = "8/8/8/8/8/8/8/K6k b - -".parse().unwrap();
+        assert!(epd.operations.is_empty());
+    }
+
+    #[test]
+    fn test_write_epd() {
+        let txt = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - am O-O Rxa8+; c0 \"Don't; castle\"; ce -35; \
+                   dm 2; acn 1000; pm; sv e4 \"a b\"; hmvc 7; fmvn 31;";
+        let epd: Epd = txt.parse().unwrap();
+        assert_eq!(epd.to_string(), txt);
+        assert_eq!(epd.to_string().parse::<Epd>().unwrap(), epd);
+
+        let mut epd = Epd::new(epd.board.clone());
+        epd.board.halfmove_clock = 0;
+        epd.board.fullmove_number = 1;
+        epd.set(Operation::Id("first".to_string()));
+        epd.set(Operation::Id("second".to_string()));
+        epd.set(Operation::BestMoves(vec![
+            Move::from_uci("e1g1", &epd.board).unwrap(),
+        ]));
+        assert_eq!(
+            epd.to_string(),
+            "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - id \"second\"; bm O-O;"
+        );
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let error = |txt: &str| txt.parse::<Epd>().unwrap_err();
+
+        assert_eq!(error(""), EpdError::MissingField);
+        assert_eq!(error("8/8/8/8/8/8/8/K6k w -"), EpdError::MissingField);
+        assert_eq!(
+            error("8/8/8/8/8/8/8/K6k x - -"),
+            EpdError::InvalidPosition("8/8/8/8/8/8/8/K6k x - -".to_string())
+        );
+        assert_eq!(
+            error("8/8/8/8/8/8/8/K6k w - - bm Kh1;"),
+            EpdError::InvalidMove {
+                opcode: "bm".to_string(),
+                err: SanParseError::Illegal("Kh1".to_string()),
+            }
+        );
+        assert_eq!(
+            error("8/8/8/8/8/8/8/K6k w - - ce 1.5;"),
+            EpdError::InvalidOperand {
+                opcode: "ce".to_string(),
+                operand: "1.5".to_string(),
+            }
+        );
+        assert_eq!(
+            error("8/8/8/8/8/8/8/K6k w - - dm -1;"),
+            EpdError::InvalidOperand {
+                opcode: "dm".to_string(),
+                operand: "-1".to_string(),
+            }
+        );
+        assert_eq!(
+            error("8/8/8/8/8/8/8/K6k w - - id;"),
+            EpdError::MissingOperand("id".to_string())
+        );
+        assert_eq!(
+            error("8/8/8/8/8/8/8/K6k w - - id \"WAC;"),
+            EpdError::UnterminatedString
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! A game as a sequence of moves from a start position, remembering every position on the way.
+
+use crate::Board;
+use crate::board::Undo;
+use crate::moves::{Move, MoveParseError};
+use crate::outcome::Outcome;
+use crate::pgn::{PgnGame, PgnNode};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Game {
+    start: Board,
+    board: Board,
+    moves: Vec<Move>,
+    /// The hash of the position before each move, so `hashes[i]` is where `moves[i]` was played.
+    hashes: Vec<u64>,
+    undos: Vec<Undo>,
+}
+
+impl Game {
+    pub fn new(start: Board) -> Self {
+        Self {
+            board: start.clone(),
+            start,
+            moves: Vec::new(),
+            hashes: Vec::new(),
+            undos: Vec::new(),
+        }
+    }
+
+    pub fn start_pos() -> Self {
+        Self::new(Board::start_pos())
+    }
+
+    /// The position the game started from.
+    pub fn start(&self) -> &Board {
+        &self.start
+    }
+
+    /// The current position.
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// The moves played so far, oldest first.
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    /// The hashes of the positions before the current one, oldest first.
+    pub fn history(&self) -> &[u64] {
+        &self.hashes
+    }
+
+    /// How many moves have been played since the start position.
+    pub fn ply(&self) -> usize {
+        self.moves.len()
+    }
+
+    /// Plays a move, which has to be legal in the current position.
+    pub fn push(&mut self, mov: Move) {
+        debug_assert!(
+            self.board.is_legal(mov),
+            "{mov} is illegal in {}",
+            self.board
+        );
+
+        self.hashes.push(self.board.hash());
+        self.undos.push(self.board.make_move(mov));
+        self.moves.push(mov);
+    }
+
+    /// Plays a move in UCI notation, checking that it is legal.
+    pub fn push_uci(&mut self, txt: &str) -> Result<Move, MoveParseError> {
+        let mov = Move::from_uci(txt, &self.board)?;
+        self.push(mov);
+        Ok(mov)
+    }
+
+    /// Takes back the last move, if there is one.
+    pub fn pop(&mut self) -> Option<Move> {
+        let mov = self.moves.pop()?;
+        self.hashes.pop();
+        self.board.unmake_move(mov, self.undos.pop().unwrap());
+        Some(mov)
+    }
+
+    /// How many times the current position has come up, counting this time.
+    pub fn repetitions(&self) -> usize {
+        self.board.repetitions(&self.hashes)
+    }
+
+    /// How the game stands, including draws by repetition.
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.board.outcome_with_history(&self.hashes)
+    }
+
+    /// The FEN of the current position.
+    pub fn to_fen(&self) -> String {
+        self.board.to_fen()
+    }
+
+    /// The game as PGN, with no tags and the result filled in if the game is over without any
+    /// claim.
+    pub fn to_pgn(&self) -> PgnGame {
+        let mut pgn = PgnGame::new(self.start.clone());
+        pgn.moves.nodes = self.moves.iter().copied().map(PgnNode::new).collect();
+
+        if let Some(outcome) = self.outcome()
+            && outcome.is_automatic()
+        {
+            pgn.result = outcome.result();
+        }
+
+        pgn
+    }
+}
+
+impl From<&PgnGame> for Game {
+    /// Follows the main line of the game.
+    fn from(pgn: &PgnGame) -> Self {
+        let mut game = Game::new(pgn.start.clone());
+        for mov in pgn.mainline() {
+            game.push(mov);
+        }
+        game
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Game;
+    use crate::Board;
+    use crate::outcome::Outcome;
+    use crate::pgn::{GameResult, PgnGame};
+
+    #[test]
+    fn test_push_pop() {
+        let mut game = Game::start_pos();
+
+        for uci in ["e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "a7a6", "e1g1"] {
+            game.push_uci(uci).unwrap();
+        }
+        assert_eq!(game.ply(), 7);
+        assert_eq!(
+            game.to_fen(),
+            "r1bqkbnr/1ppp1ppp/p1n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 1 4"
+        );
+        assert!(game.push_uci("e1g1").is_err());
+        assert_eq!(game.ply(), 7);
+
+        while game.pop().is_some() {}
+        assert_eq!(game.board(), game.start());
+        assert_eq!(game.board(), &Board::start_pos());
+        assert!(game.history().is_empty());
+    }
+
+    #[test]
+    fn test_game_repetitions() {
+        let mut game = Game::start_pos();
+
+        for uci in ["g1f3", "g8f6", "f3g1", "f6g8"].iter().cycle().take(8) {
+            assert_eq!(game.outcome(), None);
+            game.push_uci(uci).unwrap();
+        }
+        assert_eq!(game.repetitions(), 3);
+        assert_eq!(game.outcome(), Some(Outcome::ThreefoldRepetition));
+
+        // Claimable draws aren't written as the result
+        assert_eq!(game.to_pgn().result, GameResult::Unknown);
+
+        game.pop();
+        assert_eq!(game.repetitions(), 2);
+        assert_eq!(game.outcome(), None);
+    }
+
+    #[test]
+    fn test_game_pgn() {
+        let mut game = Game::start_pos();
+        for uci in ["f2f3", "e7e5", "g2g4", "d8h4"] {
+            game.push_uci(uci).unwrap();
+        }
+
+        let pgn = game.to_pgn();
+        assert_eq!(pgn.result, GameResult::BlackWins);
+        assert_eq!(pgn.mainline().collect::<Vec<_>>(), game.moves());
+
+        let pgn: PgnGame = pgn.to_string().parse().unwrap();
+        assert_eq!(Game::from(&pgn), game);
+    }
+}
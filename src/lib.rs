@@ -1,13 +1,30 @@
+pub mod bitboard;
 pub mod board;
 pub mod coord;
+pub mod epd;
+pub mod game;
 pub mod moves;
+pub mod ordering;
+pub mod outcome;
+pub mod pgn;
+pub mod san;
+pub mod score;
+pub mod see;
+pub mod tt;
+mod zobrist;
 
 pub use board::Board;
 pub use coord::Coord;
+pub use game::Game;
+pub use score::Score;
 
 use std::{error::Error, fmt::Display};
 
-use crate::moves::{gen_at_coord_illegal, gen_moves};
+use crate::bitboard::Bits;
+use crate::moves::{Move, MoveList, gen_captures, gen_evasions, gen_moves};
+use crate::ordering::{Heuristics, MovePicker, is_quiet};
+use crate::see::piece_value;
+use crate::tt::{Bound, TranspositionTable};
 
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -146,97 +163,472 @@ impl From<Piece> for char {
 
 pub struct AbortedError;
 
-pub fn negamax(
-    alpha: f32,
-    beta: f32,
-    depth: u8,
-    board: &Board,
-    start: &std::time::Instant,
-    max_time: u128,
-) -> Result<f32, AbortedError> {
-    if start.elapsed().as_millis() > max_time {
-        return Err(AbortedError);
+/// What a search carries from node to node.
+pub struct Search<'a> {
+    /// The game up to the node being searched.
+    pub game: Game,
+    pub tt: &'a mut TranspositionTable,
+    pub heuristics: &'a mut Heuristics,
+    pub start: std::time::Instant,
+    /// In milliseconds since `start`.
+    pub max_time: u128,
+    /// Every position searched, including those in quiescence search.
+    pub nodes: u64,
+    /// The positions searched in quiescence search.
+    pub qnodes: u64,
+    /// Whether quiescence search skips captures that lose material by [`Board::see`].
+    pub see_pruning: bool,
+}
+
+/// How much a capture in quiescence search has to be able to win on top of what it takes for
+/// it to be worth searching when it can't otherwise bring the score up to alpha.
+const DELTA_MARGIN: i32 = 200;
+
+impl<'a> Search<'a> {
+    pub fn new(
+        game: Game,
+        tt: &'a mut TranspositionTable,
+        heuristics: &'a mut Heuristics,
+        max_time: u128,
+    ) -> Self {
+        Self {
+            game,
+            tt,
+            heuristics,
+            start: std::time::Instant::now(),
+            max_time,
+            nodes: 0,
+            qnodes: 0,
+            see_pruning: true,
+        }
     }
 
-    let my_color = board.to_move;
+    /// The move the transposition table remembers for the current position, if it is still
+    /// legal there. Hash collisions can hand back moves from other positions.
+    pub fn tt_move(&self) -> Option<Move> {
+        let board = self.game.board();
+        self.tt
+            .probe(board.hash())
+            .map(|entry| entry.mov)
+            .filter(|&mov| mov != Move::NULL && board.is_pseudo_legal(mov) && board.is_legal(mov))
+    }
 
-    if depth == 0 {
-        let mut heuristic = 0.0;
+    /// The legal moves of the current position, in the order [`MovePicker`] would try them.
+    pub fn root_moves(&self) -> Vec<Move> {
+        let board = self.game.board();
+        let prev = self.game.moves().last().copied();
+
+        MovePicker::new(
+            gen_moves(board),
+            board,
+            self.tt_move(),
+            self.heuristics,
+            0,
+            prev,
+        )
+        .collect()
+    }
 
-        for rank in 0..8 {
-            for file in 0..8 {
-                let coord = Coord::from_rf(rank, file).unwrap();
+    /// Searches each of `moves`, the legal moves of the current position, `depth` plies deep,
+    /// with `depth` at least 1. The best move so far is kept as alpha, so later moves only have
+    /// to prove they are no better. The best move of an earlier depth is moved to the front
+    /// first, and wins ties.
+    ///
+    /// Returns the best move and its score, or `None` when there are no moves.
+    pub fn root(
+        &mut self,
+        moves: &mut [Move],
+        depth: u8,
+    ) -> Result<Option<(Move, Score)>, AbortedError> {
+        if let Some(tt_move) = self.tt_move()
+            && let Some(index) = moves.iter().position(|&mov| mov == tt_move)
+        {
+            moves[..=index].rotate_right(1);
+        }
 
-                let piece = board[coord];
+        let mut best = None;
+        let mut alpha = -Score::INFINITE;
 
-                let mut value = match piece {
-                    Piece::PawnW | Piece::PawnB => 1.0,
+        for &mov in moves.iter() {
+            self.game.push(mov);
+            let value = self.negamax(-Score::INFINITE, -alpha, depth - 1, 1);
+            self.game.pop();
 
-                    Piece::KnightW | Piece::KnightB | Piece::BishopW | Piece::BishopB => 3.0,
+            let value = -value?;
 
-                    Piece::RookW | Piece::RookB => 5.0,
+            if value > alpha {
+                alpha = value;
+                best = Some((mov, value));
+            }
+        }
 
-                    Piece::QueenW | Piece::QueenB => 9.0,
+        if let Some((mov, score)) = best {
+            let hash = self.game.board().hash();
+            self.tt
+                .store(hash, mov, depth, score.to_tt(0), Bound::Exact);
+        }
 
-                    _ => continue,
-                };
+        Ok(best)
+    }
 
-                if piece.to_color(Color::White) == Piece::KingW {
-                    value += file.abs_diff(4) as f32 / 5.0;
+    /// Searches the current position one ply deeper at a time until time runs out, the depth
+    /// reaches `u8::MAX` or the result can't change any more: a mate is found, or the game is
+    /// already drawn whatever is played. `report` is called with each finished depth, its best
+    /// move and its score.
+    ///
+    /// Returns the best move of the deepest finished depth, or `None` when there are no moves
+    /// or not even depth 1 finished.
+    pub fn iterative_deepening(
+        &mut self,
+        mut report: impl FnMut(&Self, u8, Move, Score),
+    ) -> Option<Move> {
+        // Ordered once up front, after that the best move of each depth moves to the front
+        let mut moves = self.root_moves();
+        let settled = self
+            .game
+            .outcome()
+            .is_some_and(|outcome| outcome.is_automatic());
+
+        let mut best = None;
+        for depth in 1..=u8::MAX {
+            if self.start.elapsed().as_millis() >= self.max_time {
+                break;
+            }
+            let Ok(Some((mov, score))) = self.root(&mut moves, depth) else {
+                break;
+            };
 
-                    if rank != 0 && rank != 7 {
-                        value -= 3.0;
-                    }
-                } else if piece == Piece::PawnW {
-                    value += coord.rank() as f32 / 7.0;
-                } else if piece == Piece::PawnB {
-                    value += (7 - coord.rank()) as f32 / 7.0
-                } else {
-                    let mut moves = Vec::new();
-                    gen_at_coord_illegal(board, coord, &mut moves);
-                    value += moves.len() as f32 / 10.0;
-                }
+            report(self, depth, mov, score);
+            best = Some(mov);
+
+            if settled || score.is_mate() {
+                break;
+            }
+        }
+
+        best
+    }
+
+    /// Searches `depth` plies below the current position of the game, which is `ply` plies from
+    /// the root of the search.
+    pub fn negamax(
+        &mut self,
+        alpha: Score,
+        beta: Score,
+        depth: u8,
+        ply: usize,
+    ) -> Result<Score, AbortedError> {
+        if self.start.elapsed().as_millis() > self.max_time {
+            return Err(AbortedError);
+        }
+
+        let board = self.game.board();
+
+        // Anything the side to move could do from a repeated position, it could have done the
+        // first time round too, so count the repetition as a draw straight away
+        if board.repetitions(self.game.history()) >= 2 {
+            return Ok(Score::DRAW);
+        }
 
-                if piece.get_color() != my_color {
-                    value = -value;
+        if depth == 0 {
+            return self.quiescence(alpha, beta, ply);
+        }
+        if ply >= score::MAX_PLY {
+            return Ok(evaluate(board));
+        }
+
+        self.nodes += 1;
+        let board = self.game.board();
+        let hash = board.hash();
+        if let Some(entry) = self.tt.probe(hash)
+            && entry.depth >= depth
+        {
+            let score = entry.score.from_tt(ply);
+            match entry.bound {
+                Bound::Exact => return Ok(score.clamp(alpha, beta)),
+                Bound::Lower if score >= beta => return Ok(beta),
+                Bound::Upper if score <= alpha => return Ok(alpha),
+                _ => {}
+            }
+        }
+
+        let moves = gen_moves(board);
+
+        if moves.is_empty() {
+            return if board.in_check() {
+                Ok(Score::mated_in(ply))
+            } else {
+                Ok(Score::DRAW)
+            };
+        }
+
+        // Only after looking for mate, which still counts on the last move before the claim
+        if board.halfmove_clock >= 100 {
+            return Ok(Score::DRAW);
+        }
+
+        let prev = self.game.moves().last().copied();
+        let picker = MovePicker::new(moves, board, self.tt_move(), self.heuristics, ply, prev);
+
+        let mut alpha = alpha;
+        let mut best_move = Move::NULL;
+        let mut quiets_tried = MoveList::new();
+
+        for mov in picker {
+            self.game.push(mov);
+            let value = self.negamax(-beta, -alpha, depth - 1, ply + 1);
+            self.game.pop();
+
+            let value = -value?;
+
+            if value >= beta {
+                if is_quiet(mov) {
+                    self.heuristics.update_cutoff(
+                        self.game.board(),
+                        prev,
+                        ply,
+                        depth,
+                        mov,
+                        &quiets_tried,
+                    );
                 }
+                self.tt
+                    .store(hash, mov, depth, beta.to_tt(ply), Bound::Lower);
+                return Ok(beta);
+            }
+
+            if is_quiet(mov) {
+                quiets_tried.push(mov);
+            }
 
-                heuristic += value;
+            if value > alpha {
+                alpha = value;
+                best_move = mov;
             }
         }
 
-        return Ok(heuristic);
+        let bound = if best_move == Move::NULL {
+            Bound::Upper
+        } else {
+            Bound::Exact
+        };
+        self.tt
+            .store(hash, best_move, depth, alpha.to_tt(ply), bound);
+
+        Ok(alpha)
     }
-    let moves = gen_moves(board);
 
-    if moves.is_empty() {
-        let mut board = board.clone();
-        board.to_move = board.to_move.flip();
-        let moves = gen_moves(&board);
+    /// Searches only captures and promotions below the current position, `ply` plies from the
+    /// root, until it is quiet enough to trust the static evaluation. The side to move can
+    /// always stand pat instead of capturing, unless it is in check, where every evasion is
+    /// searched.
+    pub fn quiescence(
+        &mut self,
+        alpha: Score,
+        beta: Score,
+        ply: usize,
+    ) -> Result<Score, AbortedError> {
+        if self.start.elapsed().as_millis() > self.max_time {
+            return Err(AbortedError);
+        }
+
+        self.nodes += 1;
+        self.qnodes += 1;
+
+        let board = self.game.board();
+
+        if ply >= score::MAX_PLY {
+            return Ok(evaluate(board));
+        }
 
-        return if board.check_check(&moves, my_color.flip()) {
-            Ok(f32::NEG_INFINITY)
+        let mut alpha = alpha;
+        let mut stand_pat = None;
+
+        let moves = if board.in_check() {
+            let moves = gen_evasions(board);
+            if moves.is_empty() {
+                return Ok(Score::mated_in(ply));
+            }
+            moves
         } else {
-            Ok(0.0)
+            let eval = evaluate(board);
+            if eval >= beta {
+                return Ok(beta);
+            }
+            alpha = alpha.max(eval);
+            stand_pat = Some(eval);
+
+            gen_captures(board)
         };
+
+        let prev = self.game.moves().last().copied();
+        let picker = MovePicker::new(moves, board, None, self.heuristics, ply, prev);
+
+        for mov in picker {
+            let board = self.game.board();
+
+            if let Some(eval) = stand_pat {
+                // Delta pruning: not even winning the piece and then some reaches alpha
+                let mut gain = piece_value(board[mov.dst()]);
+                if mov.is_en_passant() {
+                    gain = piece_value(Piece::PawnW);
+                }
+                if let Some(promotion) = mov.promotion() {
+                    gain += piece_value(promotion) - piece_value(Piece::PawnW);
+                }
+                if !alpha.is_mate() && eval.0 + gain + DELTA_MARGIN < alpha.0 {
+                    continue;
+                }
+
+                if self.see_pruning && !board.see_ge(mov, 0) {
+                    continue;
+                }
+            }
+
+            self.game.push(mov);
+            let value = self.quiescence(-beta, -alpha, ply + 1);
+            self.game.pop();
+
+            let value = -value?;
+
+            if value >= beta {
+                return Ok(beta);
+            }
+
+            if value > alpha {
+                alpha = value;
+            }
+        }
+
+        Ok(alpha)
     }
+}
 
-    let mut alpha = alpha;
+/// A static guess at how good the position is for the side to move.
+pub fn evaluate(board: &Board) -> Score {
+    let my_color = board.to_move;
+    let mut heuristic = 0;
+
+    for coord in Bits(board.occupied()) {
+        let piece = board[coord];
+
+        let mut value = match piece {
+            Piece::PawnW | Piece::PawnB => 100,
 
-    for mov in moves {
-        let mut board = board.clone();
-        board.make_move(mov);
+            Piece::KnightW | Piece::KnightB | Piece::BishopW | Piece::BishopB => 300,
 
-        let value = -negamax(-beta, -alpha, depth - 1, &board, start, max_time)?;
+            Piece::RookW | Piece::RookB => 500,
+
+            Piece::QueenW | Piece::QueenB => 900,
+
+            _ => continue,
+        };
+
+        if piece.to_color(Color::White) == Piece::KingW {
+            value += coord.file().abs_diff(4) as i32 * 20;
+
+            if coord.rank() != 0 && coord.rank() != 7 {
+                value -= 300;
+            }
+        } else if piece == Piece::PawnW {
+            value += coord.rank() as i32 * 100 / 7;
+        } else if piece == Piece::PawnB {
+            value += (7 - coord.rank()) as i32 * 100 / 7
+        } else {
+            let mobility = board.attacks_from(coord) & !board.color(piece.get_color());
+            value += mobility.count_ones() as i32 * 10;
+        }
 
-        if value >= beta {
-            return Ok(beta);
+        if piece.get_color() != my_color {
+            value = -value;
         }
 
-        if value > alpha {
-            alpha = value;
+        heuristic += value;
+    }
+
+    Score::cp(heuristic)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ordering::Heuristics;
+    use crate::tt::TranspositionTable;
+    use crate::{Board, Game, Score, Search, evaluate};
+
+    fn quiescence(fen: &str, see_pruning: bool) -> (Score, Score) {
+        let board = Board::from_fen(fen).unwrap();
+        let mut tt = TranspositionTable::new(1);
+        let mut heuristics = Heuristics::new();
+        let mut search = Search::new(
+            Game::new(board.clone()),
+            &mut tt,
+            &mut heuristics,
+            u128::MAX,
+        );
+        search.see_pruning = see_pruning;
+
+        let score = search
+            .quiescence(-Score::INFINITE, Score::INFINITE, 0)
+            .ok()
+            .unwrap();
+        assert!(search.qnodes > 0 && search.nodes >= search.qnodes);
+
+        (evaluate(&board), score)
+    }
+
+    /// The depths and scores [`Search::iterative_deepening`] reports, with no time limit.
+    fn deepen(fen: &str) -> Vec<(u8, Score)> {
+        let mut tt = TranspositionTable::new(1);
+        let mut heuristics = Heuristics::new();
+        let mut search = Search::new(
+            Game::new(Board::from_fen(fen).unwrap()),
+            &mut tt,
+            &mut heuristics,
+            u128::MAX,
+        );
+
+        let mut reports = Vec::new();
+        let best = search.iterative_deepening(|_, depth, _, score| reports.push((depth, score)));
+        assert_eq!(best.is_some(), !reports.is_empty());
+
+        reports
+    }
+
+    #[test]
+    fn test_quiescence() {
+        for see_pruning in [true, false] {
+            // Taking the defended pawn with the queen only loses it
+            let (eval, score) = quiescence("4k3/8/3p4/4p3/8/8/8/4QK2 w - - 0 1", see_pruning);
+            assert_eq!(score, eval);
+
+            // The hanging queen gets taken
+            let (eval, score) = quiescence("4k3/8/8/4q3/8/8/8/4RK2 w - - 0 1", see_pruning);
+            assert!(score.0 > eval.0 + 800);
+
+            // Whatever the material, mate is mate
+            let (_, score) = quiescence("R5k1/5ppp/8/8/8/8/8/4K3 b - - 0 1", see_pruning);
+            assert_eq!(score, Score::mated_in(0));
         }
     }
 
-    Ok(alpha)
+    #[test]
+    fn test_iterative_deepening() {
+        // Past depth 1 every move is a draw by the fifty-move rule, so each depth finishes
+        // straight away and the depth runs out instead of the time
+        let reports = deepen("4k3/8/8/8/8/8/8/R3K3 w - - 100 80");
+        assert_eq!(reports.len(), u8::MAX as usize);
+        assert!(reports[1..].iter().all(|&(_, score)| score == Score::DRAW));
+
+        // Nothing to search for with bare kings
+        assert_eq!(
+            deepen("4k3/8/8/8/8/8/8/4K3 w - - 100 80"),
+            [(1, Score::DRAW)]
+        );
+
+        // Nor once the mate is found
+        assert_eq!(
+            deepen("6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1"),
+            [(1, Score::mate_in(1))]
+        );
+    }
 }
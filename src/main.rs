@@ -1,19 +1,24 @@
 use std::io;
+use std::sync::{Arc, Mutex};
 use std::thread;
 
 use huscee::{
-    moves::{Move, gen_moves},
+    ordering::Heuristics,
+    tt::{DEFAULT_HASH_MB, TranspositionTable},
     *,
 };
 
 fn main() {
-    let mut board = Board::start_pos();
+    bitboard::init();
+
+    let mut game = Game::start_pos();
+    let tt = Arc::new(Mutex::new(TranspositionTable::new(DEFAULT_HASH_MB)));
+    let heuristics = Arc::new(Mutex::new(Heuristics::new()));
 
     let stdin = io::stdin();
 
     let mut line = String::new();
 
-    let mut is_searching = false;
     let mut search_thread: Option<thread::JoinHandle<()>> = None;
     let mut search_time_max = 0;
 
@@ -25,59 +30,98 @@ fn main() {
         let mut line_iter = line.split_whitespace().peekable();
         while let Some(first) = line_iter.next() {
             match first {
-                "uci" => println!("uciok"),
+                "uci" => {
+                    println!(
+                        "option name Hash type spin default {DEFAULT_HASH_MB} min 1 max 65536"
+                    );
+                    println!("uciok");
+                }
+                "setoption" => {
+                    // The search holds on to the table until it is done
+                    if search_running(&mut search_thread) {
+                        println!("info string Can't change options while searching");
+                        break;
+                    }
+
+                    // Names and values can have spaces in them, so read up to the keywords
+                    let mut name = Vec::new();
+                    let mut value = Vec::new();
+                    let mut field = None;
+                    for txt in line_iter.by_ref() {
+                        match txt {
+                            "name" => field = Some(&mut name),
+                            "value" => field = Some(&mut value),
+                            _ => {
+                                if let Some(field) = &mut field {
+                                    field.push(txt);
+                                }
+                            }
+                        }
+                    }
+
+                    if name.join(" ").eq_ignore_ascii_case("Hash") {
+                        match value.join(" ").parse() {
+                            Ok(mb @ 1..=65536) => tt.lock().unwrap().resize(mb),
+                            _ => println!("info string Invalid Hash size"),
+                        }
+                    }
+                }
                 "ucinewgame" => {
-                    board = Board::start_pos();
+                    if search_running(&mut search_thread) {
+                        println!("info string Can't start a new game while searching");
+                        break;
+                    }
+
+                    game = Game::start_pos();
+                    tt.lock().unwrap().clear();
+                    heuristics.lock().unwrap().clear();
                 }
                 "isready" => {
-                    let thread = search_thread.take_if(|t| t.is_finished());
-                    if let Some(thread) = thread {
-                        thread.join().unwrap();
-                        is_searching = false;
-                    }
+                    search_running(&mut search_thread);
 
                     println!("readyok");
                 }
                 "position" => {
                     match line_iter.next().expect("Unknown command") {
-                        "startpos" => board = Board::start_pos(),
+                        "startpos" => game = Game::start_pos(),
                         "fen" => {
                             let mut fen: String = String::new();
-                            for _ in 0..6 {
-                                fen.push_str(line_iter.next().unwrap());
+                            // The move clocks are optional, so read up to the move list
+                            while let Some(field) = line_iter.next_if(|&txt| txt != "moves") {
+                                fen.push_str(field);
                                 fen.push(' ');
                             }
-                            board = Board::from_fen(&fen).unwrap()
+                            // Keep the old position rather than searching one that can't occur
+                            let new_board = Board::from_fen(&fen)
+                                .map_err(|err| err.to_string())
+                                .and_then(|new_board| {
+                                    new_board.validate().map_err(|err| err.to_string())?;
+                                    Ok(new_board)
+                                });
+                            match new_board {
+                                Ok(new_board) => game = Game::new(new_board),
+                                Err(err) => {
+                                    println!("info string {err}");
+                                    break;
+                                }
+                            }
                         }
                         _ => panic!("Unknown command"),
                     }
 
                     if line_iter.next().is_some_and(|txt| txt == "moves") {
-                        for mov in line_iter {
-                            board.make_move(Move::new(
-                                Coord::from_alg(&mov[..2]).unwrap(),
-                                Coord::from_alg(&mov[2..4]).unwrap(),
-                                if mov.len() == 4 {
-                                    None
-                                } else {
-                                    Piece::from_char(
-                                        mov.chars().nth(4).unwrap().to_ascii_lowercase(),
-                                    )
-                                    .ok()
-                                },
-                            ));
+                        for txt in line_iter {
+                            // Keep the position up to the bad move rather than corrupting it
+                            if let Err(err) = game.push_uci(txt) {
+                                println!("info string {err}, ignoring the rest of the moves");
+                                break;
+                            }
                         }
                     }
                 }
                 "go" => {
-                    let thread = search_thread.take_if(|t| t.is_finished());
-                    if let Some(thread) = thread {
-                        thread.join().unwrap();
-                        is_searching = false;
-                    }
-
-                    if is_searching {
-                        continue 'line_loop;
+                    if search_running(&mut search_thread) {
+                        break;
                     }
 
                     let mut wtime = 0;
@@ -91,7 +135,7 @@ fn main() {
                             "perft" => {
                                 let depth = line_iter.next().unwrap().parse().unwrap();
                                 let start = std::time::Instant::now();
-                                let split_map = board.perft_split(depth);
+                                let split_map = game.board().perft_split(depth);
                                 let time = start.elapsed().as_millis();
 
                                 let mut nodes = 0;
@@ -124,12 +168,12 @@ fn main() {
                             _ => break,
                         }
                     }
-                    let board = board.clone();
+                    let game = game.clone();
 
                     let mytime;
                     let myinc;
 
-                    if board.to_move == Color::White {
+                    if game.board().to_move == Color::White {
                         mytime = wtime;
                         myinc = winc;
                     } else {
@@ -143,10 +187,16 @@ fn main() {
                     }
 
                     println!("info string Searching with max time of {search_time_max}ms");
+                    let tt = Arc::clone(&tt);
+                    let heuristics = Arc::clone(&heuristics);
                     search_thread = Some(thread::spawn(move || {
-                        find_best_move(&board, search_time_max);
+                        find_best_move(
+                            &game,
+                            &mut tt.lock().unwrap(),
+                            &mut heuristics.lock().unwrap(),
+                            search_time_max,
+                        );
                     }));
-                    is_searching = true;
                 }
                 "quit" => break 'line_loop,
                 _ => continue,
@@ -161,80 +211,38 @@ fn main() {
     }
 }
 
-fn find_best_move(board: &Board, max_time_ms: u128) {
-    let start = std::time::Instant::now();
-
-    let moves = gen_moves(board);
-
-    let mut curr_best = None;
-
-    let mut depth = 1;
-
-    let mut mate_in: i16 = 0;
-
-    // TODO: Draw by 3-fold repetition
-
-    'depth_loop: while start.elapsed().as_millis() < max_time_ms {
-        let mut best = f32::NEG_INFINITY;
-        let mut best_move = None;
-
-        for &mov in moves.iter() {
-            let mut board = board.clone();
-            board.make_move(mov);
-
-            if let Ok(value) = negamax(
-                f32::NEG_INFINITY,
-                f32::INFINITY,
-                depth - 1,
-                &board,
-                &start,
-                max_time_ms,
-            ) {
-                let value = -value;
-                if value >= best {
-                    best = value;
-                    best_move = Some(mov);
-                }
-            } else {
-                println!("info string Search aborted at depth {depth}");
-                break 'depth_loop;
-            }
-        }
-
-        // TODO: Clean this up
-        if best == f32::NEG_INFINITY {
-            if mate_in == 0 {
-                mate_in = depth as i16 / 2;
-            }
-            println!(
-                "info depth {} score mate {} pv {}",
-                depth,
-                -mate_in,
-                best_move.unwrap()
-            );
-        } else if best == f32::INFINITY {
-            if mate_in == 0 {
-                mate_in = depth as i16 / 2;
-            }
-            println!(
-                "info depth {} score mate {} pv {}",
-                depth,
-                mate_in,
-                best_move.unwrap()
-            );
-        } else {
-            println!(
-                "info depth {} score cp {} pv {}",
-                depth,
-                (best * 100.0) as i64,
-                best_move.unwrap()
-            );
-        }
-
-        curr_best = best_move;
-
-        depth += 1;
+/// Joins the search thread if the search is over. Returns whether it is still running.
+fn search_running(search_thread: &mut Option<thread::JoinHandle<()>>) -> bool {
+    if let Some(thread) = search_thread.take_if(|t| t.is_finished()) {
+        thread.join().unwrap();
     }
+    search_thread.is_some()
+}
 
-    println!("bestmove {}", curr_best.unwrap());
+fn find_best_move(
+    game: &Game,
+    tt: &mut TranspositionTable,
+    heuristics: &mut Heuristics,
+    max_time_ms: u128,
+) {
+    tt.new_search();
+    heuristics.new_search();
+    let mut search = Search::new(game.clone(), tt, heuristics, max_time_ms);
+
+    let curr_best = search.iterative_deepening(|search, depth, best_move, best| {
+        println!(
+            "info depth {} score {} nodes {} hashfull {} pv {}",
+            depth,
+            best,
+            search.nodes,
+            search.tt.hashfull(),
+            best_move
+        );
+    });
+
+    // Stopped before even depth 1 finished, so go with the move ordering's first guess
+    match curr_best.or_else(|| search.root_moves().first().copied()) {
+        Some(mov) => println!("bestmove {mov}"),
+        None => println!("bestmove 0000"),
+    }
 }
@@ -1,19 +1,230 @@
 use super::*;
+use crate::bitboard::{
+    Bits, KING_ATTACKS, KNIGHT_ATTACKS, PAWN_ATTACKS, between, bishop_attacks, bit, line,
+    queen_attacks, rook_attacks,
+};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct Move {
-    pub orig: Coord,
-    pub dst: Coord,
-    pub prom_tgt: Option<Piece>,
+/// Why a move in UCI notation couldn't be read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MoveParseError {
+    /// Not the 4 or 5 characters of a UCI move.
+    InvalidLength(String),
+    InvalidSquare(String),
+    /// The fifth character isn't one of `n`, `b`, `r` or `q`.
+    InvalidPromotion(char),
+    /// Well formed, but not a legal move in the position.
+    Illegal(String),
 }
 
+impl Display for MoveParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveParseError::InvalidLength(txt) => write!(f, "Invalid move length: {txt}"),
+            MoveParseError::InvalidSquare(txt) => write!(f, "Invalid square in move: {txt}"),
+            MoveParseError::InvalidPromotion(c) => write!(f, "Invalid promotion piece: {c}"),
+            MoveParseError::Illegal(txt) => write!(f, "Illegal move: {txt}"),
+        }
+    }
+}
+
+impl Error for MoveParseError {}
+
+/// What kind of move a [`Move`] is. Stored in the top 4 bits of the move.
+///
+/// Bit 2 marks captures and bit 3 promotions; for promotions the low two bits give the piece.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MoveFlag {
+    Quiet = 0,
+    DoublePush = 1,
+    KingCastle = 2,
+    QueenCastle = 3,
+    Capture = 4,
+    EnPassant = 5,
+
+    KnightPromotion = 8,
+    BishopPromotion = 9,
+    RookPromotion = 10,
+    QueenPromotion = 11,
+
+    KnightPromotionCapture = 12,
+    BishopPromotionCapture = 13,
+    RookPromotionCapture = 14,
+    QueenPromotionCapture = 15,
+}
+
+impl MoveFlag {
+    /// The flag for promoting to the given piece (of either color), or `None` when it isn't a
+    /// piece a pawn can promote to.
+    pub fn promotion(piece: Piece, capture: bool) -> Option<Self> {
+        let idx = match piece.to_color(Color::White) {
+            Piece::KnightW => 0,
+            Piece::BishopW => 1,
+            Piece::RookW => 2,
+            Piece::QueenW => 3,
+            _ => return None,
+        };
+
+        Some((8 | (capture as u8) << 2 | idx).into())
+    }
+}
+
+impl From<u8> for MoveFlag {
+    fn from(value: u8) -> Self {
+        match value & 0xf {
+            0 => Self::Quiet,
+            1 => Self::DoublePush,
+            2 => Self::KingCastle,
+            3 => Self::QueenCastle,
+            4 => Self::Capture,
+            5 => Self::EnPassant,
+
+            8 => Self::KnightPromotion,
+            9 => Self::BishopPromotion,
+            10 => Self::RookPromotion,
+            11 => Self::QueenPromotion,
+
+            12 => Self::KnightPromotionCapture,
+            13 => Self::BishopPromotionCapture,
+            14 => Self::RookPromotionCapture,
+            15 => Self::QueenPromotionCapture,
+
+            _ => Self::Quiet,
+        }
+    }
+}
+
+/// A move packed into 16 bits: 6 bits for the origin square, 6 for the destination and 4 for
+/// the [`MoveFlag`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Move(u16);
+
 impl Move {
-    pub fn new(orig: Coord, dst: Coord, prom_tgt: Option<Piece>) -> Self {
-        Self {
-            orig,
-            dst,
-            prom_tgt,
+    /// Doesn't correspond to any real move. Can be used to mark an empty slot.
+    pub const NULL: Self = Self(0);
+
+    pub fn new(orig: Coord, dst: Coord, flag: MoveFlag) -> Self {
+        Self(orig.to_sq() as u16 | (dst.to_sq() as u16) << 6 | (flag as u16) << 12)
+    }
+
+    /// Builds a move from its origin, destination and promotion piece, working out the flag from
+    /// the position the move is played in. Returns `None` when `prom_tgt` can't be promoted to.
+    pub fn from_coords(
+        board: &Board,
+        orig: Coord,
+        dst: Coord,
+        prom_tgt: Option<Piece>,
+    ) -> Option<Self> {
+        let piece = board[orig].to_color(Color::White);
+        let capture = board[dst] != Piece::Empty;
+
+        let flag = if let Some(p) = prom_tgt {
+            MoveFlag::promotion(p, capture)?
+        } else if capture {
+            MoveFlag::Capture
+        } else if piece == Piece::PawnW && board.en_pass_tgt == Some(dst) {
+            MoveFlag::EnPassant
+        } else if piece == Piece::PawnW && orig.rank().abs_diff(dst.rank()) == 2 {
+            MoveFlag::DoublePush
+        } else if piece == Piece::KingW && orig.file() == 4 && dst.file() == 6 {
+            MoveFlag::KingCastle
+        } else if piece == Piece::KingW && orig.file() == 4 && dst.file() == 2 {
+            MoveFlag::QueenCastle
+        } else {
+            MoveFlag::Quiet
+        };
+
+        Some(Self::new(orig, dst, flag))
+    }
+
+    /// Reads a move in UCI notation (`e2e4`, `e7e8q`, castling as `e1g1`) and finds it among
+    /// the legal moves of the position.
+    pub fn from_uci(txt: &str, board: &Board) -> Result<Self, MoveParseError> {
+        if !txt.is_ascii() || !(4..=5).contains(&txt.len()) {
+            return Err(MoveParseError::InvalidLength(txt.to_string()));
+        }
+
+        let square =
+            |alg| Coord::from_alg(alg).map_err(|_| MoveParseError::InvalidSquare(txt.to_string()));
+        let orig = square(&txt[..2])?;
+        let dst = square(&txt[2..4])?;
+
+        // Some GUIs send the promotion piece in uppercase
+        let promotion = match txt[4..].chars().next() {
+            None => None,
+            Some(c) => match c.to_ascii_lowercase() {
+                'n' | 'b' | 'r' | 'q' => Some(Piece::from_char(c.to_ascii_uppercase()).unwrap()),
+                _ => return Err(MoveParseError::InvalidPromotion(c)),
+            },
+        };
+
+        gen_moves(board)
+            .into_iter()
+            .find(|mov| mov.orig() == orig && mov.dst() == dst && mov.promotion() == promotion)
+            .ok_or_else(|| MoveParseError::Illegal(txt.to_string()))
+    }
+
+    pub fn orig(self) -> Coord {
+        Coord::from_sq((self.0 & 0x3f) as usize)
+    }
+
+    pub fn dst(self) -> Coord {
+        Coord::from_sq((self.0 >> 6 & 0x3f) as usize)
+    }
+
+    pub fn flag(self) -> MoveFlag {
+        ((self.0 >> 12) as u8).into()
+    }
+
+    /// The piece a pawn promotes to, as a white piece. Use [`Piece::to_color`] to get the
+    /// piece actually put on the board.
+    pub fn promotion(self) -> Option<Piece> {
+        if !self.is_promotion() {
+            return None;
         }
+
+        Some(match self.0 >> 12 & 3 {
+            0 => Piece::KnightW,
+            1 => Piece::BishopW,
+            2 => Piece::RookW,
+            _ => Piece::QueenW,
+        })
+    }
+
+    pub fn is_promotion(self) -> bool {
+        self.0 & (8 << 12) != 0
+    }
+
+    /// Whether the move captures something, including en passant.
+    pub fn is_capture(self) -> bool {
+        self.0 & (4 << 12) != 0
+    }
+
+    pub fn is_en_passant(self) -> bool {
+        self.flag() == MoveFlag::EnPassant
+    }
+
+    pub fn is_castle(self) -> bool {
+        matches!(self.flag(), MoveFlag::KingCastle | MoveFlag::QueenCastle)
+    }
+
+    pub fn is_double_push(self) -> bool {
+        self.flag() == MoveFlag::DoublePush
+    }
+
+    /// The raw 16-bit encoding of the move.
+    pub fn to_u16(self) -> u16 {
+        self.0
+    }
+
+    pub fn from_u16(value: u16) -> Self {
+        Self(value)
+    }
+}
+
+impl std::fmt::Debug for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Move({self}, {:?})", self.flag())
     }
 }
 
@@ -22,15 +233,15 @@ impl Display for Move {
         write!(
             f,
             "{}{}{}",
-            self.orig.to_alg(),
-            self.dst.to_alg(),
-            self.prom_tgt.map_or_else(
+            self.orig().to_alg(),
+            self.dst().to_alg(),
+            self.promotion().map_or_else(
                 || "",
                 |p| match p {
-                    Piece::QueenW | Piece::QueenB => "q",
-                    Piece::KnightW | Piece::KnightB => "n",
-                    Piece::RookW | Piece::RookB => "r",
-                    Piece::BishopW | Piece::BishopB => "b",
+                    Piece::QueenW => "q",
+                    Piece::KnightW => "n",
+                    Piece::RookW => "r",
+                    Piece::BishopW => "b",
                     _ => unreachable!(),
                 }
             ),
@@ -38,256 +249,571 @@ impl Display for Move {
     }
 }
 
-pub fn gen_moves(board: &Board) -> Vec<Move> {
-    let orig_board = board.clone();
+/// The most moves any position can have is 218, so this is plenty.
+pub const MAX_MOVES: usize = 256;
 
-    let moves = gen_moves_illegal(board);
+/// A fixed-capacity list of moves that lives on the stack, so generating moves never allocates.
+/// Dereferences to a slice for indexing, iteration and sorting.
+#[derive(Clone)]
+pub struct MoveList {
+    moves: [Move; MAX_MOVES],
+    len: usize,
+}
 
-    let mut legal_moves = Vec::with_capacity(moves.len());
+impl MoveList {
+    pub fn new() -> Self {
+        Self {
+            moves: [Move::NULL; MAX_MOVES],
+            len: 0,
+        }
+    }
 
-    for mov in moves {
-        let orig = mov.orig;
-        let dst = mov.dst;
+    pub fn push(&mut self, mov: Move) {
+        self.moves[self.len] = mov;
+        self.len += 1;
+    }
 
-        let mut board = orig_board.clone();
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
 
-        if board[orig].to_color(Color::White) == Piece::KingW {
-            let orig_file = orig.file();
-            let dst_file = dst.file();
+    /// Removes all moves for which `f` returns false, keeping the order of the rest.
+    pub fn retain(&mut self, mut f: impl FnMut(Move) -> bool) {
+        let mut kept = 0;
 
-            if orig_file.abs_diff(dst_file) == 2 {
-                board.to_move = board.to_move.flip();
-                let dict = gen_moves_illegal(&board);
-                if board.check_check(&dict, board.to_move) {
-                    continue;
-                }
-                board.to_move = board.to_move.flip();
-
-                let file = orig_file.midpoint(dst_file);
-                let mut board = board.clone();
-                board.make_move(Move::new(
-                    orig,
-                    Coord::from_rf(mov.dst.rank() as usize, file as usize).unwrap(),
-                    None,
-                ));
-                let new_moves = gen_moves_illegal(&board);
-                if board.check_check(&new_moves, board.to_move) {
-                    continue;
-                }
+        for i in 0..self.len {
+            if f(self.moves[i]) {
+                self.moves[kept] = self.moves[i];
+                kept += 1;
             }
         }
-        board.make_move(mov);
-        let dict = gen_moves_illegal(&board);
-        if board.check_check(&dict, board.to_move) {
-            continue;
-        }
 
-        legal_moves.push(mov);
+        self.len = kept;
     }
+}
 
-    legal_moves
+impl Default for MoveList {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-/// Generates all possible moves of all pieces. Includes moves that expose your king to check (thus the 'illegal' part).
-pub fn gen_moves_illegal(board: &Board) -> Vec<Move> {
-    let mut moves = Vec::new();
+impl std::ops::Deref for MoveList {
+    type Target = [Move];
 
-    for rank in 0..8 {
-        for file in 0..8 {
-            let coord = Coord::try_from((rank, file)).unwrap();
+    fn deref(&self) -> &Self::Target {
+        &self.moves[..self.len]
+    }
+}
 
-            let piece = board[coord];
+impl std::ops::DerefMut for MoveList {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.moves[..self.len]
+    }
+}
 
-            if piece == Piece::Empty || piece.get_color() != board.to_move {
-                continue;
-            }
+impl std::fmt::Debug for MoveList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl PartialEq for MoveList {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for MoveList {}
 
-            gen_at_coord_illegal(board, coord, &mut moves);
+impl Extend<Move> for MoveList {
+    fn extend<T: IntoIterator<Item = Move>>(&mut self, iter: T) {
+        for mov in iter {
+            self.push(mov);
         }
     }
+}
 
-    moves
+impl FromIterator<Move> for MoveList {
+    fn from_iter<T: IntoIterator<Item = Move>>(iter: T) -> Self {
+        let mut list = Self::new();
+        list.extend(iter);
+        list
+    }
 }
 
-/// Generates all possible moves of a piece on the given square. Includes moves that expose
-/// your king to check (thus the 'illegal' part). Does not consider turn order.
-pub fn gen_at_coord_illegal(board: &Board, coord: Coord, moves: &mut Vec<Move>) {
-    let piece = board[coord];
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a Move;
+    type IntoIter = std::slice::Iter<'a, Move>;
 
-    match piece {
-        Piece::PawnW | Piece::PawnB => add_pawn_moves(board, coord, moves),
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
 
-        Piece::RookW | Piece::RookB => add_rook_moves(board, coord, moves),
+impl IntoIterator for MoveList {
+    type Item = Move;
+    type IntoIter = MoveListIntoIter;
 
-        Piece::BishopW | Piece::BishopB => add_bishop_moves(board, coord, moves),
+    fn into_iter(self) -> Self::IntoIter {
+        MoveListIntoIter { list: self, idx: 0 }
+    }
+}
 
-        Piece::QueenW | Piece::QueenB => {
-            add_rook_moves(board, coord, moves);
-            add_bishop_moves(board, coord, moves);
-        }
+pub struct MoveListIntoIter {
+    list: MoveList,
+    idx: usize,
+}
 
-        Piece::KnightW | Piece::KnightB => add_knight_moves(board, coord, moves),
+impl Iterator for MoveListIntoIter {
+    type Item = Move;
 
-        Piece::KingW | Piece::KingB => add_king_moves(board, coord, moves),
+    fn next(&mut self) -> Option<Self::Item> {
+        let mov = self.list.get(self.idx).copied();
+        self.idx += 1;
+        mov
+    }
 
-        Piece::Empty => {}
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.list.len().saturating_sub(self.idx);
+        (n, Some(n))
     }
 }
 
-fn add_pawn_moves(board: &Board, orig: Coord, moves: &mut Vec<Move>) {
-    let row_add = if board[orig].is_white() { 1 } else { -1 };
-    // normal move forward
-    if let Ok(c) = orig.add((row_add, 0))
-        && board[c] == Piece::Empty
-    {
-        // promotion
-        if c.rank() == 0 || c.rank() == 7 {
-            for p in [Piece::QueenW, Piece::KnightW, Piece::BishopW, Piece::RookW] {
-                moves.push(Move::new(
-                    orig,
-                    c,
-                    Some(p.to_color(board[orig].get_color())),
-                ))
-            }
+impl ExactSizeIterator for MoveListIntoIter {}
+
+/// Which moves a generator should produce, so search can generate moves lazily in stages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GenType {
+    All,
+    /// Captures (including en passant) and all promotions.
+    Captures,
+    /// Everything else, including castling.
+    Quiets,
+    /// All moves getting out of check. Nothing when not in check.
+    Evasions,
+    /// Quiet moves that give check, directly or by discovery.
+    QuietChecks,
+}
+
+impl GenType {
+    fn wants_captures(self) -> bool {
+        matches!(self, GenType::All | GenType::Captures | GenType::Evasions)
+    }
+
+    fn wants_quiets(self) -> bool {
+        self != GenType::Captures
+    }
+
+    fn includes(self, board: &Board, mov: Move) -> bool {
+        if mov.is_capture() || mov.is_promotion() {
+            self.wants_captures()
         } else {
-            moves.push(Move::new(orig, c, None));
+            self.wants_quiets() && (self != GenType::QuietChecks || board.gives_check(mov))
         }
+    }
+}
 
-        // first move by 2
-        if orig.rank() == if board[orig].is_white() { 1 } else { 6 }
-            && let Ok(c2) = orig.add((row_add * 2, 0))
-            && board[c2] == Piece::Empty
-        {
-            moves.push(Move::new(orig, c2, None));
+/// Generates all legal moves in the position.
+pub fn gen_moves(board: &Board) -> MoveList {
+    gen_legal(board, GenType::All)
+}
+
+/// Generates the legal captures and promotions in the position.
+pub fn gen_captures(board: &Board) -> MoveList {
+    gen_legal(board, GenType::Captures)
+}
+
+/// Generates the legal moves that neither capture nor promote.
+pub fn gen_quiets(board: &Board) -> MoveList {
+    gen_legal(board, GenType::Quiets)
+}
+
+/// Generates the moves getting the side to move out of check: king moves, captures of the
+/// checking piece and interpositions. Generates nothing when not in check.
+pub fn gen_evasions(board: &Board) -> MoveList {
+    gen_legal(board, GenType::Evasions)
+}
+
+/// Generates the legal moves that give check without capturing or promoting.
+pub fn gen_quiet_checks(board: &Board) -> MoveList {
+    gen_legal(board, GenType::QuietChecks)
+}
+
+/// Generates the legal moves of the given type.
+pub fn gen_legal(board: &Board, kind: GenType) -> MoveList {
+    let us = board.to_move;
+    let them = us.flip();
+
+    // Without a king there is nothing to keep out of check
+    let Some(king) = board.king(us) else {
+        let mut moves = gen_moves_illegal(board);
+        if kind == GenType::Evasions {
+            moves.clear();
         }
+        moves.retain(|mov| kind.includes(board, mov));
+        return moves;
+    };
+
+    let mut moves = MoveList::new();
+
+    let occupied = board.occupied();
+    let own = board.color(us);
+
+    let checkers = board.attackers(king, them, occupied);
+
+    if kind == GenType::Evasions && checkers == 0 {
+        return moves;
     }
 
-    // capture left/right
-    for file_add in [-1, 1] {
-        if let Ok(c) = orig.add((row_add, file_add))
-            && (((board[c] != Piece::Empty) && (board[c].get_color() != board[orig].get_color()))
-                || board.en_pass_tgt.is_some_and(|tgt| c == tgt))
-        {
-            if c.rank() == 0 || c.rank() == 7 {
-                for p in [Piece::QueenW, Piece::KnightW, Piece::BishopW, Piece::RookW] {
-                    moves.push(Move::new(
-                        orig,
-                        c,
-                        Some(p.to_color(board[orig].get_color())),
-                    ))
-                }
-            } else {
-                moves.push(Move::new(orig, c, None));
+    let kind_targets = match (kind.wants_captures(), kind.wants_quiets()) {
+        (true, true) => !own,
+        (true, false) => board.color(them),
+        _ => !occupied,
+    };
+
+    // For quiet checks, every piece is limited to the squares it gives check from
+    let check_info = match kind {
+        GenType::QuietChecks => match CheckInfo::new(board) {
+            Some(info) => Some(info),
+            None => return moves,
+        },
+        _ => None,
+    };
+    let check_targets = |orig: Coord, piece: Piece| {
+        check_info
+            .as_ref()
+            .map_or(!0, |info| info.targets(board, orig, piece))
+    };
+
+    // The king can't step onto attacked squares, including ones only covered through the king
+    // itself by a slider it is running away from
+    let king_targets =
+        KING_ATTACKS[king.to_sq()] & kind_targets & check_targets(king, Piece::KingW);
+    for dst in Bits(king_targets) {
+        if board.attackers(dst, them, occupied ^ bit(king)) == 0 {
+            moves.push(Move::new(king, dst, capture_flag(board, dst)));
+        }
+    }
+
+    // In double check only the king can move
+    if checkers.count_ones() > 1 {
+        return moves;
+    }
+
+    let targets = if checkers == 0 {
+        if kind.wants_quiets() {
+            let mut castles = MoveList::new();
+            add_castling_moves(board, king, &mut castles);
+            moves.extend(
+                castles
+                    .into_iter()
+                    .filter(|&mov| check_info.is_none() || board.gives_check(mov)),
+            );
+        }
+        !own
+    } else {
+        // Capture the checker or block the check
+        let checker = Bits(checkers).next().unwrap();
+        checkers | between(king, checker)
+    };
+
+    let pinned = blockers(board, king, them) & own;
+
+    for orig in Bits(own & !bit(king)) {
+        let piece = board[orig].to_color(Color::White);
+
+        let mut targets = targets & check_targets(orig, piece);
+        if pinned & bit(orig) != 0 {
+            targets &= line(king, orig);
+        }
+
+        // Pawns sort out the move type themselves, since promotions count as captures
+        if piece == Piece::PawnW {
+            add_pawn_moves(board, orig, targets, kind, &mut moves);
+
+            if kind.wants_captures()
+                && let Some(tgt) = board.en_pass_tgt
+                && PAWN_ATTACKS[us as usize][orig.to_sq()] & bit(tgt) != 0
+                && en_passant_is_legal(board, king, orig, tgt)
+            {
+                moves.push(Move::new(orig, tgt, MoveFlag::EnPassant));
             }
+
+            continue;
+        }
+
+        let targets = targets & kind_targets;
+
+        match piece {
+            Piece::RookW => add_piece_moves(
+                board,
+                orig,
+                rook_attacks(orig, occupied),
+                targets,
+                &mut moves,
+            ),
+            Piece::BishopW => add_piece_moves(
+                board,
+                orig,
+                bishop_attacks(orig, occupied),
+                targets,
+                &mut moves,
+            ),
+            Piece::QueenW => add_piece_moves(
+                board,
+                orig,
+                queen_attacks(orig, occupied),
+                targets,
+                &mut moves,
+            ),
+            Piece::KnightW => add_piece_moves(
+                board,
+                orig,
+                KNIGHT_ATTACKS[orig.to_sq()],
+                targets,
+                &mut moves,
+            ),
+
+            _ => {}
         }
     }
+
+    moves
 }
 
-fn add_rook_moves(board: &Board, orig: Coord, moves: &mut Vec<Move>) {
-    // TODO: figure out how to DRY this
-    for i in 1..8 {
-        let err = add_move(board, moves, orig, (i, 0));
-        // break on capture
-        if err == Ok(true) || err.is_err() {
-            break;
+/// Pieces of either color that are the only thing between `king` and a slider of color
+/// `snipers` aiming at it. For the king's own pieces these are pins, for the other side's pieces
+/// they are discovered checks waiting to happen.
+fn blockers(board: &Board, king: Coord, snipers: Color) -> u64 {
+    let occupied = board.occupied();
+
+    let queens = board.pieces(Piece::QueenW.to_color(snipers));
+    let rooks = board.pieces(Piece::RookW.to_color(snipers)) | queens;
+    let bishops = board.pieces(Piece::BishopW.to_color(snipers)) | queens;
+
+    let snipers = (rook_attacks(king, 0) & rooks) | (bishop_attacks(king, 0) & bishops);
+
+    let mut blockers = 0;
+
+    for sniper in Bits(snipers) {
+        let between = between(king, sniper) & occupied;
+
+        if between.count_ones() == 1 {
+            blockers |= between;
         }
     }
 
-    for i in 1..8 {
-        let err = add_move(board, moves, orig, (-i, 0));
-        // break on capture
-        if err == Ok(true) || err.is_err() {
-            break;
-        }
+    blockers
+}
+
+/// What is needed to tell which quiet moves give check.
+struct CheckInfo {
+    /// The enemy king.
+    king: Coord,
+    /// Pieces of the side to move that give a discovered check by leaving the line to the
+    /// enemy king.
+    discoverers: u64,
+}
+
+impl CheckInfo {
+    fn new(board: &Board) -> Option<Self> {
+        let king = board.king(board.to_move.flip())?;
+        let discoverers = blockers(board, king, board.to_move) & board.color(board.to_move);
+
+        Some(Self { king, discoverers })
     }
 
-    for j in 1..8 {
-        let err = add_move(board, moves, orig, (0, j));
-        // break on capture
-        if err == Ok(true) || err.is_err() {
-            break;
+    /// The squares the piece on `orig` gives check from. `piece` is its white version.
+    fn targets(&self, board: &Board, orig: Coord, piece: Piece) -> u64 {
+        let sq = self.king.to_sq();
+        let occupied = board.occupied();
+
+        let direct = match piece {
+            Piece::PawnW => PAWN_ATTACKS[board.to_move.flip() as usize][sq],
+            Piece::KnightW => KNIGHT_ATTACKS[sq],
+            Piece::BishopW => bishop_attacks(self.king, occupied),
+            Piece::RookW => rook_attacks(self.king, occupied),
+            Piece::QueenW => queen_attacks(self.king, occupied),
+            _ => 0,
+        };
+
+        if self.discoverers & bit(orig) != 0 {
+            direct | !line(self.king, orig)
+        } else {
+            direct
         }
     }
+}
 
-    for j in 1..8 {
-        let err = add_move(board, moves, orig, (0, -j));
-        // break on capture
-        if err == Ok(true) || err.is_err() {
-            break;
-        }
+/// En passant removes two pieces from the same rank at once, which can expose the king in ways
+/// pins don't catch. Simply play the capture on the occupancy and look for attackers.
+fn en_passant_is_legal(board: &Board, king: Coord, orig: Coord, tgt: Coord) -> bool {
+    let victim = Coord::from_rf(orig.rank() as usize, tgt.file() as usize).unwrap();
+    let occupied = (board.occupied() ^ bit(orig) ^ bit(victim)) | bit(tgt);
+
+    board.attackers(king, board.to_move.flip(), occupied) & !bit(victim) == 0
+}
+
+/// Generates all possible moves of all pieces. Includes moves that expose your king to check (thus the 'illegal' part).
+pub fn gen_moves_illegal(board: &Board) -> MoveList {
+    let mut moves = MoveList::new();
+
+    for coord in Bits(board.color(board.to_move)) {
+        gen_at_coord_illegal(board, coord, &mut moves);
     }
+
+    moves
 }
 
-fn add_bishop_moves(board: &Board, orig: Coord, moves: &mut Vec<Move>) {
-    for i in 1..8 {
-        let err = add_move(board, moves, orig, (i, i));
-        // break on capture
-        if err == Ok(true) || err.is_err() {
-            break;
-        }
+/// Generates all possible moves of a piece on the given square. Includes moves that expose
+/// your king to check (thus the 'illegal' part). Does not consider turn order.
+pub fn gen_at_coord_illegal(board: &Board, coord: Coord, moves: &mut MoveList) {
+    let piece = board[coord];
+
+    if piece == Piece::Empty {
+        return;
     }
 
-    for i in 1..8 {
-        let err = add_move(board, moves, orig, (i, -i));
-        // break on capture
-        if err == Ok(true) || err.is_err() {
-            break;
+    let targets = !board.color(piece.get_color());
+    let occupied = board.occupied();
+
+    match piece.to_color(Color::White) {
+        Piece::PawnW => {
+            add_pawn_moves(board, coord, targets, GenType::All, moves);
+
+            if let Some(tgt) = board.en_pass_tgt
+                && PAWN_ATTACKS[piece.get_color() as usize][coord.to_sq()] & bit(tgt) != 0
+            {
+                moves.push(Move::new(coord, tgt, MoveFlag::EnPassant));
+            }
+        }
+
+        Piece::RookW => {
+            add_piece_moves(board, coord, rook_attacks(coord, occupied), targets, moves)
         }
+
+        Piece::BishopW => add_piece_moves(
+            board,
+            coord,
+            bishop_attacks(coord, occupied),
+            targets,
+            moves,
+        ),
+
+        Piece::QueenW => {
+            add_piece_moves(board, coord, queen_attacks(coord, occupied), targets, moves)
+        }
+
+        Piece::KnightW => {
+            add_piece_moves(board, coord, KNIGHT_ATTACKS[coord.to_sq()], targets, moves)
+        }
+
+        Piece::KingW => {
+            add_piece_moves(board, coord, KING_ATTACKS[coord.to_sq()], targets, moves);
+            add_castling_moves(board, coord, moves);
+        }
+
+        _ => {}
     }
+}
+
+/// Adds pawn pushes and captures of the given type landing on `targets`, expanding promotions.
+/// En passant is left to the caller.
+fn add_pawn_moves(board: &Board, orig: Coord, targets: u64, kind: GenType, moves: &mut MoveList) {
+    let color = board[orig].get_color();
+    let row_add = if color == Color::White { 1 } else { -1 };
+    let occupied = board.occupied();
+
+    let mut dsts = PAWN_ATTACKS[color as usize][orig.to_sq()] & board.color(color.flip());
+
+    // normal move forward
+    if let Ok(c) = orig.add((row_add, 0))
+        && occupied & bit(c) == 0
+    {
+        dsts |= bit(c);
 
-    for i in 1..8 {
-        let err = add_move(board, moves, orig, (-i, i));
-        // break on capture
-        if err == Ok(true) || err.is_err() {
-            break;
+        // first move by 2
+        if orig.rank() == if color == Color::White { 1 } else { 6 }
+            && let Ok(c2) = orig.add((row_add * 2, 0))
+            && occupied & bit(c2) == 0
+        {
+            dsts |= bit(c2);
         }
     }
 
-    for i in 1..8 {
-        let err = add_move(board, moves, orig, (-i, -i));
-        // break on capture
-        if err == Ok(true) || err.is_err() {
-            break;
+    for dst in Bits(dsts & targets) {
+        let capture = occupied & bit(dst) != 0;
+
+        let promotion = dst.rank() == 0 || dst.rank() == 7;
+
+        if capture || promotion {
+            if !kind.wants_captures() {
+                continue;
+            }
+        } else if !kind.wants_quiets() {
+            continue;
+        }
+
+        // promotion
+        if promotion {
+            for p in [Piece::QueenW, Piece::KnightW, Piece::BishopW, Piece::RookW] {
+                let flag = MoveFlag::promotion(p, capture).unwrap();
+                moves.push(Move::new(orig, dst, flag))
+            }
+        } else if capture {
+            moves.push(Move::new(orig, dst, MoveFlag::Capture));
+        } else if orig.rank().abs_diff(dst.rank()) == 2 {
+            moves.push(Move::new(orig, dst, MoveFlag::DoublePush));
+        } else {
+            moves.push(Move::new(orig, dst, MoveFlag::Quiet));
         }
     }
 }
 
-fn add_knight_moves(board: &Board, orig: Coord, moves: &mut Vec<Move>) {
-    let jumps = [
-        (2, 1),
-        (2, -1),
-        (1, 2),
-        (-1, 2),
-        (-2, 1),
-        (-2, -1),
-        (-1, -2),
-        (1, -2),
-    ];
-
-    for (i, j) in jumps {
-        _ = add_move(board, moves, orig, (i, j));
+/// Adds a move to every square in `attacks` that is also in `targets`. Works for any piece
+/// other than pawns.
+fn add_piece_moves(board: &Board, orig: Coord, attacks: u64, targets: u64, moves: &mut MoveList) {
+    for dst in Bits(attacks & targets) {
+        moves.push(Move::new(orig, dst, capture_flag(board, dst)));
     }
 }
 
-fn add_king_moves(board: &Board, orig: Coord, moves: &mut Vec<Move>) {
-    for i in -1..=1 {
-        for j in -1..=1 {
-            let _ = add_move(board, moves, orig, (i, j));
-        }
+fn capture_flag(board: &Board, dst: Coord) -> MoveFlag {
+    if board[dst] == Piece::Empty {
+        MoveFlag::Quiet
+    } else {
+        MoveFlag::Capture
     }
+}
 
+/// Adds castling moves for the king on `orig`. Checks that the squares between the king and the
+/// rook are empty and, for the side to move, that the king doesn't castle out of, through or
+/// into check.
+pub(crate) fn add_castling_moves(board: &Board, orig: Coord, moves: &mut MoveList) {
     let color = board[orig].get_color();
     let color_int = color as usize;
     let rank = orig.rank() as usize;
 
+    let safe = |file: usize| {
+        color != board.to_move
+            || !board.is_square_attacked(Coord::from_rf(rank, file).unwrap(), color.flip())
+    };
+
     // Castle kingside
     if board.castling[color_int * 2]
         && board[Coord::try_from((rank, 7)).unwrap()] == Piece::RookW.to_color(color)
         && board[Coord::try_from((rank, 6)).unwrap()] == Piece::Empty
         && board[Coord::try_from((rank, 5)).unwrap()] == Piece::Empty
+        && safe(4)
+        && safe(5)
+        && safe(6)
     {
-        add_move(board, moves, orig, (0, 2))
-            .expect("Castling destination was empty, but it also wasn't?");
+        moves.push(Move::new(
+            orig,
+            Coord::from_rf(rank, 6).unwrap(),
+            MoveFlag::KingCastle,
+        ));
     }
 
     // Castle queenside
@@ -296,43 +822,323 @@ fn add_king_moves(board: &Board, orig: Coord, moves: &mut Vec<Move>) {
         && board[Coord::try_from((rank, 1)).unwrap()] == Piece::Empty
         && board[Coord::try_from((rank, 2)).unwrap()] == Piece::Empty
         && board[Coord::try_from((rank, 3)).unwrap()] == Piece::Empty
+        && safe(4)
+        && safe(3)
+        && safe(2)
     {
-        add_move(board, moves, orig, (0, -2))
-            .expect("Castling destination was empty, but it also wasn't?");
-    }
-}
-
-/// Returns Ok(true) if the move is a capture, Ok(false) if it isn't and Err(()) if the move is
-/// invalid.
-fn add_move(
-    board: &Board,
-    moves: &mut Vec<Move>,
-    orig: Coord,
-    add: (isize, isize),
-) -> Result<bool, ()> {
-    if let Ok(c) = orig.add(add)
-        && (board[c] == Piece::Empty || board[c].get_color() != board[orig].get_color())
-    {
-        moves.push(Move::new(orig, c, None));
-        Ok(board[c] != Piece::Empty)
-    } else {
-        Err(())
+        moves.push(Move::new(
+            orig,
+            Coord::from_rf(rank, 2).unwrap(),
+            MoveFlag::QueenCastle,
+        ));
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{
+        Move, MoveFlag, MoveList, MoveParseError, gen_captures, gen_evasions, gen_moves,
+        gen_quiet_checks, gen_quiets,
+    };
+    use crate::bitboard::bit;
     use crate::board::Board;
+    use crate::{Coord, Piece};
     use paste::paste;
 
     macro_rules! test_battery {
         ($({$name:ident, $fen:expr, $expected:tt}),*) => {
             $(
                 huscee_macros::perft_test!($name, $fen, $expected);
+
+                paste! {
+                    #[test]
+                    fn [<$name _hash>]() {
+                        check_hash(&Board::from_fen($fen).unwrap(), 3);
+                    }
+
+                    #[test]
+                    fn [<$name _staged>]() {
+                        check_staged(&mut Board::from_fen($fen).unwrap(), 2);
+                    }
+
+                    #[test]
+                    fn [<$name _unmake>]() {
+                        check_unmake(&mut Board::from_fen($fen).unwrap(), 3);
+                    }
+
+                    #[test]
+                    fn [<$name _fen>]() {
+                        assert_eq!(Board::from_fen($fen).unwrap().to_fen(), $fen);
+                        check_fen(&Board::from_fen($fen).unwrap(), 2);
+                    }
+                }
             )*
         }
     }
 
+    #[test]
+    fn test_move_encoding() {
+        let sq = |alg| Coord::from_alg(alg).unwrap();
+
+        let mov = Move::new(sq("e7"), sq("f8"), MoveFlag::KnightPromotionCapture);
+        assert_eq!(mov.orig(), sq("e7"));
+        assert_eq!(mov.dst(), sq("f8"));
+        assert_eq!(mov.flag(), MoveFlag::KnightPromotionCapture);
+        assert_eq!(mov.promotion(), Some(Piece::KnightW));
+        assert!(mov.is_capture());
+        assert!(!mov.is_en_passant());
+        assert_eq!(mov.to_string(), "e7f8n");
+        assert_eq!(Move::from_u16(mov.to_u16()), mov);
+
+        let board = Board::from_fen("r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap();
+        assert_eq!(
+            Move::from_coords(&board, sq("e5"), sq("d6"), None)
+                .unwrap()
+                .flag(),
+            MoveFlag::EnPassant
+        );
+        assert_eq!(
+            Move::from_coords(&board, sq("e1"), sq("c1"), None)
+                .unwrap()
+                .flag(),
+            MoveFlag::QueenCastle
+        );
+        assert_eq!(
+            Move::from_coords(&board, sq("a1"), sq("a8"), None)
+                .unwrap()
+                .flag(),
+            MoveFlag::Capture
+        );
+
+        for mov in gen_moves(&board) {
+            assert_eq!(
+                Move::from_coords(&board, mov.orig(), mov.dst(), mov.promotion()),
+                Some(mov)
+            );
+        }
+
+        // Pawns only promote to knights, bishops, rooks and queens
+        let board = Board::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        for piece in [Piece::PawnW, Piece::KingB, Piece::Empty] {
+            assert_eq!(
+                Move::from_coords(&board, sq("b7"), sq("b8"), Some(piece)),
+                None
+            );
+        }
+        assert_eq!(
+            Move::from_coords(&board, sq("b7"), sq("b8"), Some(Piece::RookB)),
+            Some(Move::new(sq("b7"), sq("b8"), MoveFlag::RookPromotion))
+        );
+    }
+
+    #[test]
+    fn test_move_from_uci() {
+        let board = Board::from_fen("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap();
+        let uci = |txt: &str| Move::from_uci(txt, &board);
+
+        assert_eq!(uci("e1g1").unwrap().flag(), MoveFlag::KingCastle);
+        assert_eq!(uci("e1c1").unwrap().flag(), MoveFlag::QueenCastle);
+        assert_eq!(uci("e5d6").unwrap().flag(), MoveFlag::EnPassant);
+        assert_eq!(
+            uci("b7a8n").unwrap().flag(),
+            MoveFlag::KnightPromotionCapture
+        );
+        assert_eq!(uci("b7b8q").unwrap().flag(), MoveFlag::QueenPromotion);
+        assert_eq!(uci("b7b8Q"), uci("b7b8q"));
+        assert_eq!(uci("b7a8N"), uci("b7a8n"));
+
+        for mov in gen_moves(&board) {
+            assert_eq!(uci(&mov.to_string()), Ok(mov));
+        }
+
+        assert!(matches!(uci(""), Err(MoveParseError::InvalidLength(_))));
+        assert!(matches!(uci("e2"), Err(MoveParseError::InvalidLength(_))));
+        assert!(matches!(
+            uci("e1g1qq"),
+            Err(MoveParseError::InvalidLength(_))
+        ));
+        assert!(matches!(uci("é1g1"), Err(MoveParseError::InvalidLength(_))));
+        assert!(matches!(uci("i1g1"), Err(MoveParseError::InvalidSquare(_))));
+        assert!(matches!(uci("e0g1"), Err(MoveParseError::InvalidSquare(_))));
+        assert!(matches!(uci("E1G1"), Err(MoveParseError::InvalidSquare(_))));
+        assert_eq!(uci("b7b8k"), Err(MoveParseError::InvalidPromotion('k')));
+        assert_eq!(uci("b7b8K"), Err(MoveParseError::InvalidPromotion('K')));
+        assert!(matches!(uci("b7b8"), Err(MoveParseError::Illegal(_))));
+        assert!(matches!(uci("e1e2q"), Err(MoveParseError::Illegal(_))));
+        assert!(matches!(uci("e1h1"), Err(MoveParseError::Illegal(_))));
+        assert!(matches!(uci("a8a7"), Err(MoveParseError::Illegal(_))));
+    }
+
+    #[test]
+    fn test_move_list() {
+        let mut moves = gen_moves(&Board::start_pos());
+        assert_eq!(moves.len(), 20);
+
+        moves.sort_by_key(|mov| mov.to_string());
+        assert_eq!(moves[0].to_string(), "a2a3");
+        assert_eq!(moves.iter().last().unwrap().to_string(), "h2h4");
+
+        moves.retain(|mov| mov.orig().rank() == 0);
+        assert_eq!(moves.len(), 4);
+        assert_eq!(moves.into_iter().count(), 4);
+    }
+
+    #[test]
+    fn test_check_generators() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ];
+
+        // Random playouts, with a fixed seed so failures can be reproduced
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for fen in fens {
+            for _ in 0..50 {
+                let mut board = Board::from_fen(fen).unwrap();
+
+                for _ in 0..100 {
+                    check_checks(&mut board);
+
+                    let moves = gen_moves(&board);
+                    if moves.is_empty() {
+                        break;
+                    }
+                    board.make_move(moves[random() as usize % moves.len()]);
+                }
+            }
+        }
+    }
+
+    /// Walks the move tree and checks that the incrementally updated hash and bitboards always
+    /// match the ones computed from scratch.
+    fn check_hash(board: &Board, depth: usize) {
+        assert_eq!(board.hash(), board.compute_hash(), "{board}");
+
+        let mut occupied = 0;
+        for sq in 0..64 {
+            let coord = Coord::from_sq(sq);
+            let piece = board[coord];
+            if piece != Piece::Empty {
+                assert_ne!(board.pieces(piece) & bit(coord), 0, "{board}");
+                assert_ne!(board.color(piece.get_color()) & bit(coord), 0, "{board}");
+                occupied |= bit(coord);
+            }
+        }
+        assert_eq!(board.occupied(), occupied, "{board}");
+
+        if depth == 0 {
+            return;
+        }
+
+        for mov in gen_moves(board) {
+            let mut board = board.clone();
+            board.make_move(mov);
+            check_hash(&board, depth - 1);
+        }
+    }
+
+    /// Walks the move tree and checks that captures and quiets split the legal moves exactly.
+    fn check_staged(board: &mut Board, depth: usize) {
+        let moves = gen_moves(board);
+        let captures = gen_captures(board);
+        let quiets = gen_quiets(board);
+
+        assert_eq!(captures.len() + quiets.len(), moves.len(), "{board}");
+        for mov in &captures {
+            assert!(mov.is_capture() || mov.is_promotion(), "{board} {mov}");
+            assert!(moves.contains(mov), "{board} {mov}");
+        }
+        for mov in &quiets {
+            assert!(!mov.is_capture() && !mov.is_promotion(), "{board} {mov}");
+            assert!(moves.contains(mov), "{board} {mov}");
+        }
+
+        if depth == 0 {
+            return;
+        }
+
+        for mov in moves {
+            let undo = board.make_move(mov);
+            check_staged(board, depth - 1);
+            board.unmake_move(mov, undo);
+        }
+    }
+
+    /// Checks the evasion and quiet check generators, and [`Board::gives_check`], against
+    /// filtering all legal moves.
+    fn check_checks(board: &mut Board) {
+        let moves = gen_moves(board);
+
+        let mut expected = if board.in_check() {
+            moves.clone()
+        } else {
+            MoveList::new()
+        };
+        let mut evasions = gen_evasions(board);
+        expected.sort_by_key(|mov| mov.to_u16());
+        evasions.sort_by_key(|mov| mov.to_u16());
+        assert_eq!(evasions, expected, "{board}");
+
+        let mut expected = MoveList::new();
+        for mov in moves {
+            let gives_check = board.gives_check(mov);
+
+            let undo = board.make_move(mov);
+            assert_eq!(gives_check, board.in_check(), "{board} {mov}");
+            board.unmake_move(mov, undo);
+
+            if gives_check && !mov.is_capture() && !mov.is_promotion() {
+                expected.push(mov);
+            }
+        }
+        let mut checks = gen_quiet_checks(board);
+        expected.sort_by_key(|mov| mov.to_u16());
+        checks.sort_by_key(|mov| mov.to_u16());
+        assert_eq!(checks, expected, "{board}");
+    }
+
+    /// Walks the move tree and checks that unmaking a move restores the board exactly.
+    fn check_unmake(board: &mut Board, depth: usize) {
+        if depth == 0 {
+            return;
+        }
+
+        for mov in gen_moves(board) {
+            let before = board.clone();
+            let undo = board.make_move(mov);
+            check_unmake(board, depth - 1);
+            board.unmake_move(mov, undo);
+            assert_eq!(board, &before, "{mov}");
+        }
+    }
+
+    /// Walks the move tree and checks that every position survives a round trip through FEN and
+    /// passes validation.
+    fn check_fen(board: &Board, depth: usize) {
+        assert_eq!(&Board::from_fen(&board.to_fen()).unwrap(), board);
+        assert_eq!(board.validate(), Ok(()), "{board}");
+
+        if depth == 0 {
+            return;
+        }
+
+        for mov in gen_moves(board) {
+            let mut board = board.clone();
+            board.make_move(mov);
+            check_fen(&board, depth - 1);
+        }
+    }
+
     // NOTE: Positions taken from the Chess Programming Wiki
     //
     test_battery!(
@@ -0,0 +1,265 @@
+//! Move ordering: trying the moves most likely to be best first, so alpha-beta cuts off sooner.
+//!
+//! The order is the transposition table move, captures that don't lose material by MVV-LVA,
+//! queen promotions, the killer moves, the countermove, the other quiet moves by history, losing
+//! captures and finally underpromotions.
+
+use super::*;
+use crate::moves::{MAX_MOVES, Move, MoveList};
+use crate::score::MAX_PLY;
+use crate::see::piece_value;
+
+/// History scores stay within plus or minus this.
+const MAX_HISTORY: i32 = 16_384;
+
+const TT_MOVE: i32 = 4_000_000;
+const GOOD_CAPTURE: i32 = 2_000_000;
+const PROMOTION: i32 = 1_500_000;
+const KILLER: i32 = 1_000_000;
+const COUNTERMOVE: i32 = 900_000;
+const BAD_CAPTURE: i32 = -100_000;
+const UNDERPROMOTION: i32 = -200_000;
+
+/// Whether the move is ordered among the quiet moves, by killers and history.
+pub fn is_quiet(mov: Move) -> bool {
+    !mov.is_capture() && !mov.is_promotion()
+}
+
+/// What the search learns about moves as it goes, for ordering them in other positions.
+#[derive(Clone)]
+pub struct Heuristics {
+    /// Quiet moves that caused a cutoff at each ply, the latest first.
+    killers: [[Move; 2]; MAX_PLY],
+    /// How often quiet moves caused cutoffs, by side to move, origin and destination.
+    history: [[[i32; 64]; 64]; 2],
+    /// The quiet move that last refuted each move, by the piece that moved and its destination.
+    countermoves: [[Move; 64]; 16],
+}
+
+impl Heuristics {
+    pub fn new() -> Self {
+        Self {
+            killers: [[Move::NULL; 2]; MAX_PLY],
+            history: [[[0; 64]; 64]; 2],
+            countermoves: [[Move::NULL; 64]; 16],
+        }
+    }
+
+    /// Empties the killers, zeroes the history and drops every countermove, so nothing learned
+    /// in an earlier game steers the ordering. Unlike [`Heuristics::new_search`], the history is
+    /// wiped rather than halved.
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Prepares for a new search: killers are only good for positions at the same ply, and
+    /// history from earlier searches counts for less.
+    pub fn new_search(&mut self) {
+        self.killers = [[Move::NULL; 2]; MAX_PLY];
+        for score in self.history.iter_mut().flatten().flatten() {
+            *score /= 2;
+        }
+    }
+
+    pub fn killers(&self, ply: usize) -> [Move; 2] {
+        self.killers.get(ply).copied().unwrap_or([Move::NULL; 2])
+    }
+
+    pub fn history(&self, color: Color, mov: Move) -> i32 {
+        self.history[color as usize][mov.orig().to_sq()][mov.dst().to_sq()]
+    }
+
+    /// The move that last refuted `prev`, the move leading to the position on the board.
+    pub fn countermove(&self, board: &Board, prev: Option<Move>) -> Move {
+        match prev {
+            Some(prev) => self.countermoves[board[prev.dst()] as usize][prev.dst().to_sq()],
+            None => Move::NULL,
+        }
+    }
+
+    /// Moves the history score towards the bonus, slowing down as it nears the limit.
+    fn add_history(&mut self, color: Color, mov: Move, bonus: i32) {
+        let score = &mut self.history[color as usize][mov.orig().to_sq()][mov.dst().to_sq()];
+        *score += bonus - *score * bonus.abs() / MAX_HISTORY;
+    }
+
+    /// Learns from the quiet move `mov` causing a cutoff at `depth`, `ply` plies from the root,
+    /// after the quiet moves in `tried` had failed to.
+    pub fn update_cutoff(
+        &mut self,
+        board: &Board,
+        prev: Option<Move>,
+        ply: usize,
+        depth: u8,
+        mov: Move,
+        tried: &[Move],
+    ) {
+        if let Some(killers) = self.killers.get_mut(ply)
+            && killers[0] != mov
+        {
+            killers[1] = killers[0];
+            killers[0] = mov;
+        }
+
+        if let Some(prev) = prev {
+            self.countermoves[board[prev.dst()] as usize][prev.dst().to_sq()] = mov;
+        }
+
+        let bonus = (depth as i32 * depth as i32).min(1200);
+        self.add_history(board.to_move, mov, bonus);
+        for &other in tried {
+            self.add_history(board.to_move, other, -bonus);
+        }
+    }
+}
+
+impl Default for Heuristics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Hands out moves best first. Picks the next best move each time rather than sorting, since
+/// after a cutoff the rest are never needed.
+pub struct MovePicker {
+    moves: MoveList,
+    scores: [i32; MAX_MOVES],
+    next: usize,
+}
+
+impl MovePicker {
+    /// Orders moves of the position on the board, `ply` plies from the root and reached by
+    /// `prev`.
+    pub fn new(
+        moves: MoveList,
+        board: &Board,
+        tt_move: Option<Move>,
+        heuristics: &Heuristics,
+        ply: usize,
+        prev: Option<Move>,
+    ) -> Self {
+        let killers = heuristics.killers(ply);
+        let countermove = heuristics.countermove(board, prev);
+
+        let mut scores = [0; MAX_MOVES];
+        for (score, &mov) in scores.iter_mut().zip(moves.iter()) {
+            *score = if Some(mov) == tt_move {
+                TT_MOVE
+            } else if mov.is_capture() {
+                let victim = if mov.is_en_passant() {
+                    Piece::PawnW
+                } else {
+                    board[mov.dst()]
+                };
+                // Most valuable victim first, taken by the least valuable attacker
+                let mvv_lva = piece_value(victim) - piece_value(board[mov.orig()]) / 100;
+
+                if board.see_ge(mov, 0) {
+                    GOOD_CAPTURE + mvv_lva
+                } else {
+                    BAD_CAPTURE + mvv_lva
+                }
+            } else if let Some(promotion) = mov.promotion() {
+                if promotion == Piece::QueenW {
+                    PROMOTION
+                } else {
+                    UNDERPROMOTION
+                }
+            } else if mov == killers[0] {
+                KILLER + 1
+            } else if mov == killers[1] {
+                KILLER
+            } else if mov == countermove {
+                COUNTERMOVE
+            } else {
+                heuristics.history(board.to_move, mov)
+            };
+        }
+
+        Self {
+            moves,
+            scores,
+            next: 0,
+        }
+    }
+}
+
+impl Iterator for MovePicker {
+    type Item = Move;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let len = self.moves.len();
+        if self.next == len {
+            return None;
+        }
+
+        let best = (self.next..len).max_by_key(|&i| self.scores[i])?;
+        self.moves.swap(self.next, best);
+        self.scores.swap(self.next, best);
+        self.next += 1;
+
+        Some(self.moves[self.next - 1])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Heuristics, MovePicker};
+    use crate::Board;
+    use crate::moves::{Move, gen_moves};
+
+    fn picked(board: &Board, tt_move: Option<&str>, heuristics: &Heuristics) -> Vec<String> {
+        let tt_move = tt_move.map(|uci| Move::from_uci(uci, board).unwrap());
+        MovePicker::new(gen_moves(board), board, tt_move, heuristics, 0, None)
+            .map(|mov| mov.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_order() {
+        // Two ways to take the free queen, and a rook that would lose itself for a pawn
+        let board = Board::from_fen("4k3/8/1p6/p2q4/4P3/2N5/8/R3K3 w - - 0 1").unwrap();
+        let mut heuristics = Heuristics::new();
+
+        let moves = picked(&board, None, &heuristics);
+        assert_eq!(moves.len(), gen_moves(&board).len());
+        assert_eq!(moves[..2], ["e4d5", "c3d5"]);
+        assert_eq!(moves.last().unwrap(), "a1a5");
+
+        let moves = picked(&board, Some("e1f1"), &heuristics);
+        assert_eq!(moves[..3], ["e1f1", "e4d5", "c3d5"]);
+
+        // A cutoff makes a killer, and the moves tried before it lose history
+        let killer = Move::from_uci("e1f2", &board).unwrap();
+        let tried = Move::from_uci("e1e2", &board).unwrap();
+        heuristics.update_cutoff(&board, None, 0, 4, killer, &[tried]);
+        assert_eq!(heuristics.killers(0)[0], killer);
+        assert!(heuristics.history(board.to_move, killer) > 0);
+        assert!(heuristics.history(board.to_move, tried) < 0);
+
+        let moves = picked(&board, None, &heuristics);
+        assert_eq!(moves[..3], ["e4d5", "c3d5", "e1f2"]);
+        assert_eq!(moves[moves.len() - 2], "e1e2");
+
+        heuristics.new_search();
+        assert_eq!(heuristics.killers(0), [Move::NULL; 2]);
+        assert!(heuristics.history(board.to_move, killer) > 0);
+    }
+
+    #[test]
+    fn test_countermove() {
+        let mut board = Board::start_pos();
+        let prev = Move::from_uci("e2e4", &board).unwrap();
+        board.make_move(prev);
+
+        let mut heuristics = Heuristics::new();
+        let reply = Move::from_uci("c7c5", &board).unwrap();
+        heuristics.update_cutoff(&board, Some(prev), 1, 2, reply, &[]);
+        assert_eq!(heuristics.countermove(&board, Some(prev)), reply);
+
+        // Another ply, so no killer, but still the countermove
+        let moves: Vec<_> =
+            MovePicker::new(gen_moves(&board), &board, None, &heuristics, 3, Some(prev)).collect();
+        assert_eq!(moves[0], reply);
+    }
+}
@@ -0,0 +1,233 @@
+//! Detecting the end of a game.
+
+use super::*;
+use crate::moves::gen_moves;
+use crate::pgn::GameResult;
+
+/// The dark squares, starting with a1.
+const DARK_SQUARES: u64 = 0xaa55_aa55_aa55_aa55;
+
+/// How a game ended, or can be ended by a claim.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Checkmate {
+        winner: Color,
+    },
+    Stalemate,
+    /// Neither side can ever checkmate, whatever they play.
+    InsufficientMaterial,
+    /// Fifty moves by each side without a capture or pawn move. Drawn if claimed.
+    FiftyMoveRule,
+    /// Seventy-five moves by each side without a capture or pawn move.
+    SeventyFiveMoveRule,
+    /// The same position for the third time. Drawn if claimed.
+    ThreefoldRepetition,
+    /// The same position for the fifth time.
+    FivefoldRepetition,
+}
+
+impl Outcome {
+    pub fn winner(self) -> Option<Color> {
+        match self {
+            Outcome::Checkmate { winner } => Some(winner),
+            _ => None,
+        }
+    }
+
+    /// Whether the game is over without either player having to claim anything.
+    pub fn is_automatic(self) -> bool {
+        !matches!(self, Outcome::FiftyMoveRule | Outcome::ThreefoldRepetition)
+    }
+
+    pub fn result(self) -> GameResult {
+        match self.winner() {
+            Some(Color::White) => GameResult::WhiteWins,
+            Some(Color::Black) => GameResult::BlackWins,
+            None => GameResult::Draw,
+        }
+    }
+}
+
+impl Board {
+    /// How the game stands in this position, not knowing the positions before it. See
+    /// [`Board::outcome_with_history`] to also detect repetitions.
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.outcome_with_history(&[])
+    }
+
+    /// How the game stands in this position. `history` holds the hashes of the positions
+    /// before it, oldest first, and is used to detect repetitions.
+    ///
+    /// Automatic outcomes win over claimable ones, and checkmate wins over everything.
+    pub fn outcome_with_history(&self, history: &[u64]) -> Option<Outcome> {
+        if gen_moves(self).is_empty() {
+            return Some(if self.in_check() {
+                Outcome::Checkmate {
+                    winner: self.to_move.flip(),
+                }
+            } else {
+                Outcome::Stalemate
+            });
+        }
+
+        if self.is_insufficient_material() {
+            return Some(Outcome::InsufficientMaterial);
+        }
+
+        let repetitions = self.repetitions(history);
+
+        if repetitions >= 5 {
+            Some(Outcome::FivefoldRepetition)
+        } else if self.halfmove_clock >= 150 {
+            Some(Outcome::SeventyFiveMoveRule)
+        } else if repetitions >= 3 {
+            Some(Outcome::ThreefoldRepetition)
+        } else if self.halfmove_clock >= 100 {
+            Some(Outcome::FiftyMoveRule)
+        } else {
+            None
+        }
+    }
+
+    /// How many times this position has come up, counting this time. `history` holds the
+    /// hashes of the positions before it, oldest first.
+    pub fn repetitions(&self, history: &[u64]) -> usize {
+        // Positions before the last capture or pawn move can't come up again
+        let reversible = history.len().min(self.halfmove_clock as usize);
+
+        1 + history[history.len() - reversible..]
+            .iter()
+            .filter(|&&hash| hash == self.hash())
+            .count()
+    }
+
+    /// Whether neither side has the material left to checkmate: bare kings, a single minor
+    /// piece, or only bishops that all stand on squares of the same color.
+    pub fn is_insufficient_material(&self) -> bool {
+        let both = |piece: Piece| {
+            self.pieces(piece.to_color(Color::White)) | self.pieces(piece.to_color(Color::Black))
+        };
+
+        if both(Piece::PawnW) | both(Piece::RookW) | both(Piece::QueenW) != 0 {
+            return false;
+        }
+
+        let knights = both(Piece::KnightW);
+        let bishops = both(Piece::BishopW);
+
+        match knights.count_ones() {
+            0 => bishops & DARK_SQUARES == 0 || bishops & !DARK_SQUARES == 0,
+            1 => bishops == 0,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Outcome;
+    use crate::moves::Move;
+    use crate::pgn::GameResult;
+    use crate::{Board, Color};
+
+    #[test]
+    fn test_outcome() {
+        let outcome = |fen| Board::from_fen(fen).unwrap().outcome();
+
+        assert_eq!(
+            outcome("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            None
+        );
+        assert_eq!(
+            outcome("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3"),
+            Some(Outcome::Checkmate {
+                winner: Color::Black
+            })
+        );
+        assert_eq!(
+            outcome("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"),
+            Some(Outcome::Stalemate)
+        );
+
+        // Mate on the last move before the clock runs out still counts
+        assert_eq!(
+            outcome("7k/6Q1/6K1/8/8/8/8/8 b - - 150 100"),
+            Some(Outcome::Checkmate {
+                winner: Color::White
+            })
+        );
+        assert_eq!(outcome("7k/8/6K1/8/8/8/8/R7 b - - 99 100"), None);
+        assert_eq!(
+            outcome("7k/8/6K1/8/8/8/8/R7 b - - 100 100"),
+            Some(Outcome::FiftyMoveRule)
+        );
+        assert_eq!(
+            outcome("7k/8/6K1/8/8/8/8/R7 b - - 150 100"),
+            Some(Outcome::SeventyFiveMoveRule)
+        );
+    }
+
+    #[test]
+    fn test_insufficient_material() {
+        let insufficient = |fen| Board::from_fen(fen).unwrap().is_insufficient_material();
+
+        assert!(insufficient("8/8/4k3/8/8/3K4/8/8 w - - 0 1"));
+        assert!(insufficient("8/8/4k3/8/8/3K4/8/6N1 w - - 0 1"));
+        assert!(insufficient("8/8/4k3/8/8/3K4/8/5B2 w - - 0 1"));
+        // Bishops all on light squares, on both sides
+        assert!(insufficient("8/1b6/4k3/8/8/3K4/8/5B1B w - - 0 1"));
+
+        assert!(!insufficient("8/8/4k3/8/8/3K4/8/4BB2 w - - 0 1"));
+        assert!(!insufficient("8/2b5/4k3/8/8/3K4/8/5B2 w - - 0 1"));
+        assert!(!insufficient("8/8/4k3/8/8/3K4/8/5BN1 w - - 0 1"));
+        assert!(!insufficient("8/8/4k3/8/8/3K4/8/5NN1 w - - 0 1"));
+        assert!(!insufficient("8/8/4k3/8/8/3K4/P7/8 w - - 0 1"));
+        assert!(!insufficient("8/8/4k3/8/8/3K4/8/R7 w - - 0 1"));
+        assert_eq!(
+            Board::from_fen("8/8/4k3/8/8/3K4/8/6N1 w - - 0 1")
+                .unwrap()
+                .outcome(),
+            Some(Outcome::InsufficientMaterial)
+        );
+    }
+
+    #[test]
+    fn test_repetitions() {
+        let mut board = Board::start_pos();
+        let mut history = Vec::new();
+
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        let mut outcomes = Vec::new();
+        for uci in shuffle.iter().cycle().take(16) {
+            history.push(board.hash());
+            board.make_move(Move::from_uci(uci, &board).unwrap());
+            outcomes.push(board.outcome_with_history(&history));
+        }
+
+        assert_eq!(board.repetitions(&history), 5);
+        assert_eq!(outcomes[6], None);
+        assert_eq!(outcomes[7], Some(Outcome::ThreefoldRepetition));
+        assert_eq!(outcomes[11], Some(Outcome::ThreefoldRepetition));
+        assert_eq!(outcomes[14], Some(Outcome::ThreefoldRepetition));
+        assert_eq!(outcomes[15], Some(Outcome::FivefoldRepetition));
+
+        // A pawn move makes the earlier positions unreachable
+        history.push(board.hash());
+        board.make_move(Move::from_uci("e2e4", &board).unwrap());
+        assert_eq!(board.repetitions(&history), 1);
+    }
+
+    #[test]
+    fn test_outcome_result() {
+        assert_eq!(
+            Outcome::Checkmate {
+                winner: Color::White
+            }
+            .result(),
+            GameResult::WhiteWins
+        );
+        assert_eq!(Outcome::Stalemate.result(), GameResult::Draw);
+        assert!(Outcome::FivefoldRepetition.is_automatic());
+        assert!(!Outcome::ThreefoldRepetition.is_automatic());
+    }
+}
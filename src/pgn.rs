@@ -0,0 +1,791 @@
+//! Reading and writing games in Portable Game Notation.
+//!
+//! A game is a tree of lines: the main line holds one node per move, and every node can carry
+//! variations, which are alternative lines to its move starting from the position before it.
+
+use super::*;
+use crate::moves::Move;
+use crate::san::SanParseError;
+use std::str::FromStr;
+
+/// The seven tags every game has in export format, in the order they are written.
+const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+    ("Result", "*"),
+];
+
+/// Movetext lines are wrapped to at most this many characters.
+const LINE_WIDTH: usize = 80;
+
+/// The result written at the end of a game's movetext.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    /// Still going, abandoned or simply not known.
+    #[default]
+    Unknown,
+}
+
+impl GameResult {
+    fn from_pgn(txt: &str) -> Option<Self> {
+        match txt {
+            "1-0" => Some(GameResult::WhiteWins),
+            "0-1" => Some(GameResult::BlackWins),
+            "1/2-1/2" => Some(GameResult::Draw),
+            "*" => Some(GameResult::Unknown),
+            _ => None,
+        }
+    }
+}
+
+impl Display for GameResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Unknown => "*",
+        })
+    }
+}
+
+/// A move in a game along with its annotations.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PgnNode {
+    pub mov: Move,
+    /// Numeric annotation glyphs, like 1 for `!` or 4 for `??`.
+    pub nags: Vec<u8>,
+    /// The comment following the move.
+    pub comment: Option<String>,
+    /// Alternatives to this move.
+    pub variations: Vec<PgnLine>,
+}
+
+impl PgnNode {
+    pub fn new(mov: Move) -> Self {
+        Self {
+            mov,
+            nags: Vec::new(),
+            comment: None,
+            variations: Vec::new(),
+        }
+    }
+}
+
+/// A sequence of moves, either the main line of a game or a variation.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PgnLine {
+    /// The comment before the first move.
+    pub comment: Option<String>,
+    pub nodes: Vec<PgnNode>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PgnGame {
+    /// Tag pairs in the order they were read. `FEN` and `SetUp` are written from `start`
+    /// instead, and `Result` from `result`.
+    pub tags: Vec<(String, String)>,
+    /// The position the game starts from, taken from the `FEN` tag when there is one.
+    pub start: Board,
+    pub moves: PgnLine,
+    pub result: GameResult,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PgnErrorKind {
+    InvalidTag,
+    InvalidFen(String),
+    InvalidMove(SanParseError),
+    /// A NAG with no move before it to annotate.
+    MisplacedNag,
+    /// A variation with no move before it to be an alternative to.
+    MisplacedVariation,
+    UnterminatedComment,
+    UnterminatedVariation,
+    UnexpectedCharacter(char),
+    /// A `)` closing a variation that was never opened.
+    UnexpectedClose,
+}
+
+/// Why a PGN couldn't be read, and on which line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PgnError {
+    /// Starts at 1.
+    pub line: usize,
+    pub kind: PgnErrorKind,
+}
+
+impl Display for PgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid PGN on line {}: ", self.line)?;
+        match &self.kind {
+            PgnErrorKind::InvalidTag => write!(f, "invalid tag pair"),
+            PgnErrorKind::InvalidFen(fen) => write!(f, "invalid FEN tag {fen}"),
+            PgnErrorKind::InvalidMove(err) => write!(f, "{err}"),
+            PgnErrorKind::MisplacedNag => write!(f, "NAG without a move"),
+            PgnErrorKind::MisplacedVariation => write!(f, "variation without a move"),
+            PgnErrorKind::UnterminatedComment => write!(f, "unterminated comment"),
+            PgnErrorKind::UnterminatedVariation => write!(f, "unterminated variation"),
+            PgnErrorKind::UnexpectedCharacter(c) => write!(f, "unexpected character {c}"),
+            PgnErrorKind::UnexpectedClose => write!(f, "unexpected )"),
+        }
+    }
+}
+
+impl Error for PgnError {}
+
+impl PgnGame {
+    /// An empty game from the given position, with no tags.
+    pub fn new(start: Board) -> Self {
+        Self {
+            tags: Vec::new(),
+            start,
+            moves: PgnLine::default(),
+            result: GameResult::Unknown,
+        }
+    }
+
+    /// The value of the tag with the given name.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Sets a tag, replacing its value if the game already has it.
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// The moves of the main line.
+    pub fn mainline(&self) -> impl Iterator<Item = Move> + '_ {
+        self.moves.nodes.iter().map(|node| node.mov)
+    }
+
+    /// The position at the end of the main line.
+    pub fn end(&self) -> Board {
+        let mut board = self.start.clone();
+        for mov in self.mainline() {
+            board.make_move(mov);
+        }
+        board
+    }
+}
+
+/// Reads every game in the text.
+pub fn read_games(pgn: &str) -> Result<Vec<PgnGame>, PgnError> {
+    let mut parser = Parser {
+        tokens: tokenize(pgn)?,
+        pos: 0,
+    };
+
+    let mut games = Vec::new();
+    while parser.peek().is_some() {
+        games.push(parser.game()?);
+    }
+
+    Ok(games)
+}
+
+impl FromStr for PgnGame {
+    type Err = PgnError;
+
+    /// Reads the first game in the text.
+    fn from_str(pgn: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(pgn)?,
+            pos: 0,
+        };
+
+        parser.game()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    Open,
+    Close,
+    Result(GameResult),
+    /// A move in SAN, with any move number already taken off.
+    San(String),
+}
+
+/// Splits the text into tokens, each with the line it is on.
+fn tokenize(pgn: &str) -> Result<Vec<(Token, usize)>, PgnError> {
+    let mut tokens = Vec::new();
+
+    let mut line = 1;
+    let mut chars = pgn.chars().peekable();
+    let mut line_start = true;
+
+    let error = |line, kind| PgnError { line, kind };
+
+    while let Some(c) = chars.next() {
+        let at_line_start = line_start;
+        line_start = c == '\n';
+
+        match c {
+            '\n' => line += 1,
+            c if c.is_whitespace() => {}
+
+            // Escaped lines and rest-of-line comments
+            '%' if at_line_start => {
+                chars.by_ref().find(|&c| c == '\n');
+                line += 1;
+                line_start = true;
+            }
+            ';' => {
+                let comment: String = chars.by_ref().take_while(|&c| c != '\n').collect();
+                tokens.push((Token::Comment(comment.trim().to_string()), line));
+                line += 1;
+                line_start = true;
+            }
+
+            '{' => {
+                let start = line;
+                let mut comment = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            comment.push(c);
+                        }
+                        None => return Err(error(start, PgnErrorKind::UnterminatedComment)),
+                    }
+                }
+
+                // Comments are free text, so line breaks in them don't mean anything
+                let comment = comment.split_whitespace().collect::<Vec<_>>().join(" ");
+                tokens.push((Token::Comment(comment), start));
+            }
+
+            '[' => {
+                let mut tag = String::new();
+                let mut in_string = false;
+                let mut escaped = false;
+                loop {
+                    match chars.next() {
+                        Some(']') if !in_string => break,
+                        Some('\n') | None => return Err(error(line, PgnErrorKind::InvalidTag)),
+                        Some(c) => {
+                            in_string ^= c == '"' && !escaped;
+                            escaped = c == '\\' && !escaped;
+                            tag.push(c);
+                        }
+                    }
+                }
+
+                let (name, value) = parse_tag(&tag).ok_or(error(line, PgnErrorKind::InvalidTag))?;
+                tokens.push((Token::Tag(name, value), line));
+            }
+
+            '(' => tokens.push((Token::Open, line)),
+            ')' => tokens.push((Token::Close, line)),
+
+            '$' => {
+                let mut nag = String::new();
+                while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                    nag.push(c);
+                }
+                let nag = nag
+                    .parse()
+                    .map_err(|_| error(line, PgnErrorKind::UnexpectedCharacter('$')))?;
+                tokens.push((Token::Nag(nag), line));
+            }
+
+            c if c.is_ascii_alphanumeric() || "*!?".contains(c) => {
+                let mut symbol = String::from(c);
+                while let Some(c) =
+                    chars.next_if(|&c| !c.is_whitespace() && !"{}()[];$".contains(c))
+                {
+                    symbol.push(c);
+                }
+
+                push_symbol(&symbol, line, &mut tokens);
+            }
+
+            c => return Err(error(line, PgnErrorKind::UnexpectedCharacter(c))),
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Reads the inside of a tag pair, like `Event "Casual game"`.
+fn parse_tag(tag: &str) -> Option<(String, String)> {
+    let (name, value) = tag.trim().split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        unescaped.push(if c == '\\' { chars.next()? } else { c });
+    }
+
+    Some((name.to_string(), unescaped))
+}
+
+/// Sorts out a run of symbol characters: results, move numbers, moves and the traditional
+/// `!`/`?` suffix annotations.
+fn push_symbol(symbol: &str, line: usize, tokens: &mut Vec<(Token, usize)>) {
+    if let Some(result) = GameResult::from_pgn(symbol) {
+        tokens.push((Token::Result(result), line));
+        return;
+    }
+
+    // Move numbers, which may be stuck to the move
+    let digits = symbol.trim_start_matches(|c: char| c.is_ascii_digit());
+    let symbol = if digits.len() < symbol.len() && digits.starts_with('.') {
+        digits.trim_start_matches('.')
+    } else {
+        symbol
+    };
+
+    let san = symbol.trim_end_matches(['!', '?']);
+    let nag = match &symbol[san.len()..] {
+        "" => None,
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        // Not a standard suffix, leave it to the SAN parser to make sense of
+        _ => {
+            tokens.push((Token::San(symbol.to_string()), line));
+            return;
+        }
+    };
+
+    if !san.is_empty() {
+        tokens.push((Token::San(san.to_string()), line));
+    }
+    if let Some(nag) = nag {
+        tokens.push((Token::Nag(nag), line));
+    }
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    /// The line of the current token, or the last line when out of tokens.
+    fn line_number(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map_or(1, |(_, line)| *line)
+    }
+
+    fn error(&self, kind: PgnErrorKind) -> PgnError {
+        PgnError {
+            line: self.line_number(),
+            kind,
+        }
+    }
+
+    fn game(&mut self) -> Result<PgnGame, PgnError> {
+        let mut tags = Vec::new();
+        while let Some(Token::Tag(name, value)) = self.peek() {
+            tags.push((name.clone(), value.clone()));
+            self.pos += 1;
+        }
+
+        let start = match tags.iter().find(|(name, _)| name == "FEN") {
+            Some((_, fen)) => Board::from_fen(fen)
+                .map_err(|_| self.error(PgnErrorKind::InvalidFen(fen.clone())))?,
+            None => Board::start_pos(),
+        };
+
+        let moves = self.line(start.clone(), true)?;
+
+        let result = match self.peek() {
+            Some(&Token::Result(result)) => {
+                self.pos += 1;
+                result
+            }
+            _ => tags
+                .iter()
+                .find(|(name, _)| name == "Result")
+                .and_then(|(_, result)| GameResult::from_pgn(result))
+                .unwrap_or_default(),
+        };
+
+        Ok(PgnGame {
+            tags,
+            start,
+            moves,
+            result,
+        })
+    }
+
+    /// Reads a line of moves starting from `board`, up to the end of the game for the main line
+    /// or up to the closing parenthesis for a variation.
+    fn line(&mut self, mut board: Board, main: bool) -> Result<PgnLine, PgnError> {
+        let mut line = PgnLine::default();
+        let mut before = None;
+
+        loop {
+            match self.peek() {
+                None | Some(Token::Tag(..)) | Some(Token::Result(_)) if main => break,
+                None | Some(Token::Tag(..)) => {
+                    return Err(self.error(PgnErrorKind::UnterminatedVariation));
+                }
+                // Results inside variations don't mean anything
+                Some(Token::Result(_)) => {}
+
+                Some(Token::Close) if main => {
+                    return Err(self.error(PgnErrorKind::UnexpectedClose));
+                }
+                Some(Token::Close) => {
+                    self.pos += 1;
+                    break;
+                }
+
+                Some(Token::Open) => {
+                    let Some(before) = &before else {
+                        return Err(self.error(PgnErrorKind::MisplacedVariation));
+                    };
+                    self.pos += 1;
+                    let variation = self.line(Board::clone(before), false)?;
+                    line.nodes.last_mut().unwrap().variations.push(variation);
+                    continue;
+                }
+
+                Some(Token::Comment(comment)) => {
+                    let old = match line.nodes.last_mut() {
+                        Some(node) => &mut node.comment,
+                        None => &mut line.comment,
+                    };
+                    match old {
+                        Some(old) => {
+                            old.push(' ');
+                            old.push_str(comment);
+                        }
+                        None => *old = Some(comment.clone()),
+                    }
+                }
+
+                Some(&Token::Nag(nag)) => match line.nodes.last_mut() {
+                    Some(node) => node.nags.push(nag),
+                    None => return Err(self.error(PgnErrorKind::MisplacedNag)),
+                },
+
+                Some(Token::San(san)) => {
+                    let mov = Move::from_san(san, &board)
+                        .map_err(|err| self.error(PgnErrorKind::InvalidMove(err)))?;
+                    before = Some(board.clone());
+                    board.make_move(mov);
+                    line.nodes.push(PgnNode::new(mov));
+                }
+            }
+
+            self.pos += 1;
+        }
+
+        Ok(line)
+    }
+}
+
+impl Display for PgnGame {
+    /// Writes the game in export format.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let escape = |value: &str| value.replace('\\', "\\\\").replace('"', "\\\"");
+
+        for (name, default) in SEVEN_TAG_ROSTER {
+            let value = match name {
+                "Result" => self.result.to_string(),
+                _ => self.tag(name).unwrap_or(default).to_string(),
+            };
+            writeln!(f, "[{name} \"{}\"]", escape(&value))?;
+        }
+
+        if self.start != Board::start_pos() {
+            writeln!(f, "[SetUp \"1\"]")?;
+            writeln!(f, "[FEN \"{}\"]", self.start.to_fen())?;
+        }
+
+        for (name, value) in &self.tags {
+            let name = name.as_str();
+            if !SEVEN_TAG_ROSTER.iter().any(|(tag, _)| *tag == name)
+                && name != "SetUp"
+                && name != "FEN"
+            {
+                writeln!(f, "[{name} \"{}\"]", escape(value))?;
+            }
+        }
+        writeln!(f)?;
+
+        let mut writer = MovetextWriter::default();
+        writer.line(&self.moves, &self.start);
+        writer.push(self.result.to_string());
+
+        let mut width = 0;
+        for token in writer.tokens {
+            if width > 0 && width + token.len() + 1 > LINE_WIDTH {
+                writeln!(f)?;
+                width = 0;
+            }
+            if width > 0 {
+                write!(f, " ")?;
+                width += 1;
+            }
+            write!(f, "{token}")?;
+            width += token.len();
+        }
+        writeln!(f)
+    }
+}
+
+/// Turns a game tree into the tokens of its movetext.
+#[derive(Default)]
+struct MovetextWriter {
+    tokens: Vec<String>,
+    /// The next token goes right after the last one, without a space.
+    glue: bool,
+}
+
+impl MovetextWriter {
+    fn push(&mut self, token: String) {
+        let glue = self.glue || token == ")";
+        self.glue = token == "(";
+
+        match self.tokens.last_mut() {
+            Some(last) if glue => last.push_str(&token),
+            _ => self.tokens.push(token),
+        }
+    }
+
+    fn comment(&mut self, comment: &str) {
+        // A closing brace would end the comment early
+        self.push(format!("{{{}}}", comment.replace('}', "")));
+    }
+
+    fn line(&mut self, line: &PgnLine, board: &Board) {
+        if let Some(comment) = &line.comment {
+            self.comment(comment);
+        }
+
+        let mut board = board.clone();
+        // Black's moves only need a number when something came between them and White's move
+        let mut numbered = false;
+
+        for node in &line.nodes {
+            if board.to_move == Color::White {
+                self.push(format!("{}.", board.fullmove_number));
+            } else if !numbered {
+                self.push(format!("{}...", board.fullmove_number));
+            }
+            numbered = true;
+
+            self.push(node.mov.to_san(&board));
+            for nag in &node.nags {
+                self.push(format!("${nag}"));
+            }
+            if let Some(comment) = &node.comment {
+                self.comment(comment);
+                numbered = false;
+            }
+
+            for variation in &node.variations {
+                self.push("(".to_string());
+                self.line(variation, &board);
+                self.push(")".to_string());
+                numbered = false;
+            }
+
+            board.make_move(node.mov);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GameResult, PgnErrorKind, PgnGame, PgnNode, read_games};
+    use crate::Board;
+    use crate::moves::Move;
+
+    const IMMORTAL: &str = r#"[Event "London"]
+[Site "London ENG"]
+[Date "1851.06.21"]
+[Round "?"]
+[White "Adolf Anderssen"]
+[Black "Lionel Kieseritzky"]
+[Result "1-0"]
+[Opening "King's Gambit Accepted"]
+
+1. e4 e5 2. f4 exf4 3. Bc4 Qh4+ 4. Kf1 b5 5. Bxb5 Nf6 6. Nf3 Qh6 7. d3 Nh5 8.
+Nh4 Qg5 9. Nf5 c6 10. g4 Nf6 11. Rg1 cxb5 12. h4 Qg6 13. h5 Qg5 14. Qf3 Ng8 15.
+Bxf4 Qf6 16. Nc3 Bc5 17. Nd5 Qxb2 18. Bd6 Bxg1 19. e5 Qxa1+ 20. Ke2 Na6 21.
+Nxg7+ Kd8 22. Qf6+ Nxf6 23. Be7# 1-0
+"#;
+
+    #[test]
+    fn test_read_mainline() {
+        let game: PgnGame = IMMORTAL.parse().unwrap();
+
+        assert_eq!(game.tag("White"), Some("Adolf Anderssen"));
+        assert_eq!(game.tag("Opening"), Some("King's Gambit Accepted"));
+        assert_eq!(game.tag("ECO"), None);
+        assert_eq!(game.result, GameResult::WhiteWins);
+        assert_eq!(game.start, Board::start_pos());
+        assert_eq!(game.mainline().count(), 45);
+        assert_eq!(
+            game.end().to_fen(),
+            "r1bk3r/p2pBpNp/n4n2/1p1NP2P/6P1/3P4/P1P1K3/q5b1 b - - 1 23"
+        );
+
+        // Export format is exactly what the game was written in
+        assert_eq!(game.to_string(), IMMORTAL);
+    }
+
+    #[test]
+    fn test_read_annotations() {
+        let pgn = r#"[Event "Test"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"]
+
+{Start} 1. e4! $14 {Good; "quoted"} (1. e3 {Slow} Kd7 (1... Kf7 2. Kf2) 2. e4 ; rest of line
+) 1... Kd8?? 2.Kd2!? Ke7
+% escaped line
+*"#;
+        let game: PgnGame = pgn.parse().unwrap();
+
+        assert_eq!(game.start.to_fen(), "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+        assert_eq!(game.result, GameResult::Unknown);
+        assert_eq!(game.moves.comment.as_deref(), Some("Start"));
+        assert_eq!(game.mainline().count(), 4);
+
+        let e4 = &game.moves.nodes[0];
+        assert_eq!(e4.nags, [1, 14]);
+        assert_eq!(e4.comment.as_deref(), Some("Good; \"quoted\""));
+        assert_eq!(e4.variations.len(), 1);
+
+        let e3 = &e4.variations[0];
+        assert_eq!(e3.nodes.len(), 3);
+        assert_eq!(e3.nodes[0].comment.as_deref(), Some("Slow"));
+        assert_eq!(e3.nodes[2].comment.as_deref(), Some("rest of line"));
+        assert_eq!(e3.nodes[1].variations[0].nodes.len(), 2);
+
+        assert_eq!(game.moves.nodes[1].nags, [4]);
+        assert_eq!(game.moves.nodes[2].nags, [5]);
+
+        let written = game.to_string();
+        assert_eq!(
+            written,
+            r#"[Event "Test"]
+[Site "?"]
+[Date "????.??.??"]
+[Round "?"]
+[White "?"]
+[Black "?"]
+[Result "*"]
+[SetUp "1"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"]
+
+{Start} 1. e4 $1 $14 {Good; "quoted"} (1. e3 {Slow} 1... Kd7 (1... Kf7 2. Kf2)
+2. e4 {rest of line}) 1... Kd8 $4 2. Kd2 $5 Ke7 *
+"#
+        );
+        let read: PgnGame = written.parse().unwrap();
+        assert_eq!((&read.start, &read.moves), (&game.start, &game.moves));
+    }
+
+    #[test]
+    fn test_read_games() {
+        let pgn = format!("{IMMORTAL}\n[Event \"Second\"]\n\n1. d4 d5 1/2-1/2\n\n1. c4 *");
+        let games = read_games(&pgn).unwrap();
+
+        assert_eq!(games.len(), 3);
+        assert_eq!(games[1].tag("Event"), Some("Second"));
+        assert_eq!(games[1].result, GameResult::Draw);
+        assert!(games[2].tags.is_empty());
+        assert_eq!(games[2].mainline().count(), 1);
+
+        assert_eq!(read_games("").unwrap(), []);
+    }
+
+    #[test]
+    fn test_write_game() {
+        let mut game = PgnGame::new(Board::start_pos());
+        game.set_tag("White", "huscee");
+        game.set_tag("White", "huscee \"dev\"");
+        game.set_tag("TimeControl", "40/300");
+
+        let mut board = Board::start_pos();
+        for uci in ["f2f3", "e7e5", "g2g4", "d8h4"] {
+            let mov = Move::from_uci(uci, &board).unwrap();
+            board.make_move(mov);
+            game.moves.nodes.push(PgnNode::new(mov));
+        }
+        game.result = GameResult::BlackWins;
+
+        assert_eq!(
+            game.to_string(),
+            r#"[Event "?"]
+[Site "?"]
+[Date "????.??.??"]
+[Round "?"]
+[White "huscee \"dev\""]
+[Black "?"]
+[Result "0-1"]
+[TimeControl "40/300"]
+
+1. f3 e5 2. g4 Qh4# 0-1
+"#
+        );
+        let read: PgnGame = game.to_string().parse().unwrap();
+        assert_eq!(read.tag("White"), Some("huscee \"dev\""));
+        assert_eq!((read.moves, read.result), (game.moves, game.result));
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let error = |pgn: &str| {
+            let err = pgn.parse::<PgnGame>().unwrap_err();
+            (err.line, err.kind)
+        };
+
+        assert_eq!(error("[Event \"x]\n1. e4"), (1, PgnErrorKind::InvalidTag));
+        assert_eq!(error("[Event]"), (1, PgnErrorKind::InvalidTag));
+        assert_eq!(
+            error("[FEN \"8/8/8/8/8/8/8/8 x - - 0 1\"]"),
+            (
+                1,
+                PgnErrorKind::InvalidFen("8/8/8/8/8/8/8/8 x - - 0 1".to_string())
+            )
+        );
+        assert!(matches!(
+            error("1. e4\n2. Ke3"),
+            (2, PgnErrorKind::InvalidMove(_))
+        ));
+        assert_eq!(error("$1 e4"), (1, PgnErrorKind::MisplacedNag));
+        assert_eq!(error("(e4)"), (1, PgnErrorKind::MisplacedVariation));
+        assert_eq!(error("e4 {\n"), (1, PgnErrorKind::UnterminatedComment));
+        assert_eq!(error("e4 (d4\n"), (1, PgnErrorKind::UnterminatedVariation));
+        assert_eq!(error("e4 )"), (1, PgnErrorKind::UnexpectedClose));
+        assert_eq!(error("e4 @"), (1, PgnErrorKind::UnexpectedCharacter('@')));
+    }
+}
@@ -0,0 +1,307 @@
+//! Standard Algebraic Notation, the move format used by game records and books.
+
+use super::*;
+use crate::moves::{Move, gen_moves};
+
+/// Why a move in SAN couldn't be read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SanParseError {
+    /// Doesn't look like a move at all.
+    Invalid(String),
+    /// No legal move matches.
+    Illegal(String),
+    /// More than one legal move matches.
+    Ambiguous(String),
+}
+
+impl Display for SanParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SanParseError::Invalid(txt) => write!(f, "Invalid SAN move: {txt}"),
+            SanParseError::Illegal(txt) => write!(f, "Illegal move: {txt}"),
+            SanParseError::Ambiguous(txt) => write!(f, "Ambiguous move: {txt}"),
+        }
+    }
+}
+
+impl Error for SanParseError {}
+
+/// The SAN letter of a piece, ignoring its color.
+fn piece_letter(piece: Piece) -> char {
+    match piece.to_color(Color::White) {
+        Piece::RookW => 'R',
+        Piece::KnightW => 'N',
+        Piece::BishopW => 'B',
+        Piece::QueenW => 'Q',
+        Piece::KingW => 'K',
+        _ => 'P',
+    }
+}
+
+impl Move {
+    /// Writes the move in SAN, like `Nbd7`, `exd6`, `O-O-O`, `e8=Q+` or `Qxf7#`. The move has
+    /// to be legal in the position.
+    pub fn to_san(self, board: &Board) -> String {
+        let mut san = String::new();
+
+        let orig = self.orig();
+        let dst = self.dst();
+        let piece = board[orig].to_color(Color::White);
+
+        if self.is_castle() {
+            san.push_str(if dst.file() == 6 { "O-O" } else { "O-O-O" });
+        } else if piece == Piece::PawnW {
+            if self.is_capture() {
+                san.push((orig.file() + b'a') as char);
+                san.push('x');
+            }
+            san.push_str(&dst.to_alg());
+
+            if let Some(promotion) = self.promotion() {
+                san.push('=');
+                san.push(piece_letter(promotion));
+            }
+        } else {
+            san.push(piece_letter(piece));
+
+            // Only name as much of the origin as needed to tell the move apart from moves of
+            // other pieces of the same type to the same square
+            let others = gen_moves(board).into_iter().filter(|mov| {
+                mov.dst() == dst && mov.orig() != orig && board[mov.orig()] == board[orig]
+            });
+            let (mut any, mut same_file, mut same_rank) = (false, false, false);
+            for mov in others {
+                any = true;
+                same_file |= mov.orig().file() == orig.file();
+                same_rank |= mov.orig().rank() == orig.rank();
+            }
+
+            if any && (!same_file || same_rank) {
+                san.push((orig.file() + b'a') as char);
+            }
+            if same_file {
+                san.push((orig.rank() + b'1') as char);
+            }
+
+            if self.is_capture() {
+                san.push('x');
+            }
+            san.push_str(&dst.to_alg());
+        }
+
+        let mut board = board.clone();
+        board.make_move(self);
+        if board.in_check() {
+            san.push(if gen_moves(&board).is_empty() {
+                '#'
+            } else {
+                '+'
+            });
+        }
+
+        san
+    }
+
+    /// Reads a move in SAN and finds it among the legal moves of the position.
+    ///
+    /// Lenient about what people actually write: check and mate markers, annotations like `!?`,
+    /// `e.p.`, missing or wrong capture markers, `=` in promotions being left out, castling with
+    /// zeros and over-specified origins are all accepted. Falls back to UCI notation.
+    pub fn from_san(txt: &str, board: &Board) -> Result<Self, SanParseError> {
+        let invalid = || SanParseError::Invalid(txt.to_string());
+
+        let mut san = txt.trim();
+        for suffix in ["e.p.", "ep"] {
+            san = san.strip_suffix(suffix).unwrap_or(san).trim_end();
+        }
+        let san = san.trim_end_matches(['+', '#', '!', '?']);
+
+        let moves = gen_moves(board);
+
+        let castle = match san {
+            "O-O" | "0-0" => Some(6),
+            "O-O-O" | "0-0-0" => Some(2),
+            _ => None,
+        };
+        if let Some(file) = castle {
+            return moves
+                .into_iter()
+                .find(|mov| mov.is_castle() && mov.dst().file() == file)
+                .ok_or_else(|| SanParseError::Illegal(txt.to_string()));
+        }
+
+        if !san.is_ascii() {
+            return Err(invalid());
+        }
+
+        let mut chars = san.as_bytes();
+
+        // Anything after the destination square can only be a promotion
+        let mut promotion = None;
+        if let [rest @ .., last] = chars
+            && last.is_ascii_alphabetic()
+            && rest
+                .last()
+                .is_some_and(|c| c.is_ascii_digit() || *c == b'=')
+        {
+            promotion = Some(match last.to_ascii_uppercase() {
+                b'N' => Piece::KnightW,
+                b'B' => Piece::BishopW,
+                b'R' => Piece::RookW,
+                b'Q' => Piece::QueenW,
+                _ => return Err(invalid()),
+            });
+            chars = rest.strip_suffix(b"=").unwrap_or(rest);
+        }
+
+        let [rest @ .., file, rank] = chars else {
+            return Err(invalid());
+        };
+        let dst = std::str::from_utf8(&[*file, *rank])
+            .ok()
+            .and_then(|alg| Coord::from_alg(alg).ok())
+            .ok_or_else(invalid)?;
+
+        let (piece, rest) = match rest {
+            [
+                letter @ (b'K' | b'Q' | b'R' | b'B' | b'N' | b'P'),
+                rest @ ..,
+            ] => (Piece::from_char(*letter as char).unwrap(), rest),
+            _ => (Piece::PawnW, rest),
+        };
+
+        // What's left is whatever part of the origin was given, and maybe a capture marker
+        let mut orig_file = None;
+        let mut orig_rank = None;
+        for &c in rest {
+            match c {
+                b'a'..=b'h' if orig_file.is_none() => orig_file = Some(c - b'a'),
+                b'1'..=b'8' if orig_rank.is_none() => orig_rank = Some(c - b'1'),
+                b'x' | b'X' | b':' | b'-' => {}
+                _ => return Err(invalid()),
+            }
+        }
+
+        let mut matches = moves.into_iter().filter(|mov| {
+            board[mov.orig()].to_color(Color::White) == piece
+                && mov.dst() == dst
+                && mov.promotion() == promotion
+                && orig_file.is_none_or(|file| mov.orig().file() == file)
+                && orig_rank.is_none_or(|rank| mov.orig().rank() == rank)
+        });
+
+        match (matches.next(), matches.next()) {
+            (Some(mov), None) => Ok(mov),
+            (Some(_), Some(_)) => Err(SanParseError::Ambiguous(txt.to_string())),
+            // People sometimes write UCI where SAN is expected
+            (None, _) => Move::from_uci(txt.trim(), board)
+                .map_err(|_| SanParseError::Illegal(txt.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SanParseError;
+    use crate::Board;
+    use crate::moves::{Move, gen_moves};
+
+    #[test]
+    fn test_to_san() {
+        let san = |fen, uci| {
+            let board = Board::from_fen(fen).unwrap();
+            Move::from_uci(uci, &board).unwrap().to_san(&board)
+        };
+
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(san(start, "e2e4"), "e4");
+        assert_eq!(san(start, "g1f3"), "Nf3");
+
+        let knights = "rnbqkb1r/ppp1pppp/5n2/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1";
+        assert_eq!(san(knights, "b8c6"), "Nc6");
+        assert_eq!(san(knights, "b8d7"), "Nbd7");
+        assert_eq!(san(knights, "f6d7"), "Nfd7");
+
+        let rooks = "4k3/R7/8/8/8/8/R7/4K3 w - - 0 1";
+        assert_eq!(san(rooks, "a2a5"), "R2a5");
+        let queens = "5k2/8/8/8/Q6Q/8/8/Q3K3 w - - 0 1";
+        assert_eq!(san(queens, "a4d4"), "Qa4d4");
+        assert_eq!(san(queens, "h4d4"), "Qhd4");
+        assert_eq!(san(queens, "a1d4"), "Q1d4");
+
+        let castle = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(san(castle, "e1g1"), "O-O");
+        assert_eq!(san(castle, "e1c1"), "O-O-O");
+
+        let en_passant = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1";
+        assert_eq!(san(en_passant, "e5d6"), "exd6");
+
+        let promotion = "8/4P3/8/8/k7/8/8/4K3 w - - 0 1";
+        assert_eq!(san(promotion, "e7e8q"), "e8=Q+");
+        assert_eq!(san(promotion, "e7e8n"), "e8=N");
+
+        let mate = "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4";
+        assert_eq!(san(mate, "h5f7"), "Qxf7#");
+    }
+
+    #[test]
+    fn test_from_san() {
+        let board = Board::from_fen("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap();
+        let san = |txt: &str| Move::from_san(txt, &board).map(|mov| mov.to_string());
+
+        assert_eq!(san("O-O"), Ok("e1g1".to_string()));
+        assert_eq!(san("0-0-0+"), Ok("e1c1".to_string()));
+        assert_eq!(san("exd6 e.p."), Ok("e5d6".to_string()));
+        assert_eq!(san("exd6ep"), Ok("e5d6".to_string()));
+        assert_eq!(san("ed6"), Ok("e5d6".to_string()));
+        assert_eq!(san("bxa8=N"), Ok("b7a8n".to_string()));
+        assert_eq!(san("b8Q!?"), Ok("b7b8q".to_string()));
+        assert_eq!(san("b8=q"), Ok("b7b8q".to_string()));
+        assert_eq!(san("Rxa8+"), Ok("a1a8".to_string()));
+        assert_eq!(san("Ra1-a8"), Ok("a1a8".to_string()));
+        assert_eq!(san("Kf2"), Ok("e1f2".to_string()));
+        assert_eq!(san("e1f2"), Ok("e1f2".to_string()));
+
+        assert_eq!(san("Kd3"), Err(SanParseError::Illegal("Kd3".to_string())));
+        assert_eq!(san("b8"), Err(SanParseError::Illegal("b8".to_string())));
+        assert_eq!(san("b8=K"), Err(SanParseError::Invalid("b8=K".to_string())));
+        assert_eq!(san("Zf3"), Err(SanParseError::Invalid("Zf3".to_string())));
+        assert_eq!(san(""), Err(SanParseError::Invalid(String::new())));
+        assert_eq!(san("é"), Err(SanParseError::Invalid("é".to_string())));
+
+        let board = Board::from_fen("4k3/R7/8/8/8/8/R7/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            Move::from_san("Ra5", &board),
+            Err(SanParseError::Ambiguous("Ra5".to_string()))
+        );
+        assert_eq!(
+            Move::from_san("R7a5", &board).map(|mov| mov.to_string()),
+            Ok("a7a5".to_string())
+        );
+    }
+
+    #[test]
+    fn test_san_round_trip() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ];
+
+        for fen in fens {
+            let board = Board::from_fen(fen).unwrap();
+
+            for mov in gen_moves(&board) {
+                let mut child = board.clone();
+                child.make_move(mov);
+
+                for board in [&board, &child] {
+                    for mov in gen_moves(board) {
+                        let san = mov.to_san(board);
+                        assert_eq!(Move::from_san(&san, board), Ok(mov), "{board} {san}");
+                    }
+                }
+            }
+        }
+    }
+}
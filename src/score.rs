@@ -0,0 +1,129 @@
+//! Search scores: centipawns, with the largest values set aside for forced mates.
+
+use super::*;
+use std::ops::Neg;
+
+/// The deepest ply a mate can be found at. Scores closer to [`Score::MATE`] than this are mates.
+pub const MAX_PLY: usize = 256;
+
+/// A score from the point of view of the side to move, in centipawns.
+///
+/// Mates are stored as [`Score::MATE`] minus the number of plies from the root to the mate, so a
+/// quicker mate scores higher and being mated later scores higher than being mated sooner.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Score(pub i32);
+
+impl Score {
+    pub const DRAW: Score = Score(0);
+    /// Checkmate on the board at the root.
+    pub const MATE: Score = Score(32_000);
+    /// Above every real score, for the bounds of a search window.
+    pub const INFINITE: Score = Score(32_001);
+
+    pub fn cp(centipawns: i32) -> Self {
+        Score(centipawns)
+    }
+
+    /// Mating the opponent `ply` plies from the root.
+    pub fn mate_in(ply: usize) -> Self {
+        Score(Self::MATE.0 - ply as i32)
+    }
+
+    /// Getting mated `ply` plies from the root.
+    pub fn mated_in(ply: usize) -> Self {
+        -Self::mate_in(ply)
+    }
+
+    pub fn is_mate(self) -> bool {
+        self.0.abs() >= Self::MATE.0 - MAX_PLY as i32 && self.0.abs() <= Self::MATE.0
+    }
+
+    /// The number of moves (not plies) to the mate, negative when getting mated, like in UCI.
+    pub fn mate_moves(self) -> Option<i32> {
+        if !self.is_mate() {
+            None
+        } else if self.0 > 0 {
+            Some((Self::MATE.0 - self.0 + 1) / 2)
+        } else {
+            Some(-(Self::MATE.0 + self.0) / 2)
+        }
+    }
+
+    /// Makes a mate score relative to a node `ply` plies from the root instead of the root
+    /// itself, so it stays right when the node is reached at another ply. Use it on scores going
+    /// into a transposition table.
+    pub fn to_tt(self, ply: usize) -> Self {
+        match self.0 {
+            score if score >= Self::MATE.0 - MAX_PLY as i32 => Score(score + ply as i32),
+            score if score <= -Self::MATE.0 + MAX_PLY as i32 => Score(score - ply as i32),
+            score => Score(score),
+        }
+    }
+
+    /// Undoes [`Score::to_tt`] for a node `ply` plies from the root. Use it on scores coming out
+    /// of a transposition table.
+    pub fn from_tt(self, ply: usize) -> Self {
+        match self.0 {
+            score if score >= Self::MATE.0 - MAX_PLY as i32 => Score(score - ply as i32),
+            score if score <= -Self::MATE.0 + MAX_PLY as i32 => Score(score + ply as i32),
+            score => Score(score),
+        }
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+
+    fn neg(self) -> Self::Output {
+        Score(-self.0)
+    }
+}
+
+/// Writes the score the way UCI `info` lines want it, like `cp 35` or `mate -2`.
+impl Display for Score {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.mate_moves() {
+            Some(moves) => write!(f, "mate {moves}"),
+            None => write!(f, "cp {}", self.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Score;
+
+    #[test]
+    fn test_mate_scores() {
+        assert!(Score::mate_in(1) > Score::mate_in(3));
+        assert!(Score::mated_in(2) < Score::mated_in(4));
+        assert!(Score::mated_in(0) > -Score::INFINITE);
+        assert!(Score::cp(2000) < Score::mate_in(200));
+        assert!(!Score::cp(2000).is_mate());
+        assert!(Score::mated_in(0).is_mate());
+
+        assert_eq!(Score::mate_in(1).to_string(), "mate 1");
+        assert_eq!(Score::mate_in(3).to_string(), "mate 2");
+        assert_eq!(Score::mate_in(9).to_string(), "mate 5");
+        assert_eq!(Score::mated_in(2).to_string(), "mate -1");
+        assert_eq!(Score::mated_in(0).to_string(), "mate 0");
+        assert_eq!(Score::cp(-35).to_string(), "cp -35");
+        assert_eq!(Score::DRAW.to_string(), "cp 0");
+    }
+
+    #[test]
+    fn test_tt_adjustment() {
+        // Mate 3 plies below a node 4 plies from the root
+        let score = Score::mate_in(7);
+        assert_eq!(score.to_tt(4), Score::mate_in(3));
+        // Found again at 2 plies from the root
+        assert_eq!(score.to_tt(4).from_tt(2), Score::mate_in(5));
+
+        let score = Score::mated_in(6);
+        assert_eq!(score.to_tt(2).from_tt(2), score);
+        assert_eq!(score.to_tt(2), Score::mated_in(4));
+
+        assert_eq!(Score::cp(150).to_tt(10), Score::cp(150));
+        assert_eq!(Score::cp(150).from_tt(10), Score::cp(150));
+    }
+}
@@ -0,0 +1,152 @@
+//! Static exchange evaluation: what a capture wins once every recapture on the square is played
+//! out.
+
+use super::*;
+use crate::bitboard::{Bits, bit};
+use crate::moves::Move;
+
+/// What a piece is worth in an exchange, in centipawns. The king is worth more than everything
+/// else together, so it only ever captures last.
+pub fn piece_value(piece: Piece) -> i32 {
+    match piece.to_color(Color::White) {
+        Piece::PawnW => 100,
+        Piece::KnightW | Piece::BishopW => 300,
+        Piece::RookW => 500,
+        Piece::QueenW => 900,
+        Piece::KingW => 20_000,
+        _ => 0,
+    }
+}
+
+/// Piece types from least to most valuable, the order they join an exchange in.
+const EXCHANGE_ORDER: [Piece; 6] = [
+    Piece::PawnW,
+    Piece::KnightW,
+    Piece::BishopW,
+    Piece::RookW,
+    Piece::QueenW,
+    Piece::KingW,
+];
+
+impl Board {
+    /// What the move wins in material, in centipawns, if both sides keep recapturing on the
+    /// destination with their least valuable piece for as long as it pays off. Pieces moving
+    /// off a line uncover the sliders behind them.
+    ///
+    /// Pins and checks are ignored. Quiet moves score what they lose if the piece gets taken.
+    pub fn see(&self, mov: Move) -> i32 {
+        if mov.is_castle() {
+            return 0;
+        }
+
+        let orig = mov.orig();
+        let dst = mov.dst();
+
+        let mut occupied = self.occupied() ^ bit(orig);
+        let mut gain = [0; 32];
+
+        if mov.is_en_passant() {
+            let victim = Coord::from_sq(orig.to_sq() / 8 * 8 + dst.file() as usize);
+            occupied ^= bit(victim);
+            gain[0] = piece_value(Piece::PawnW);
+        } else {
+            gain[0] = piece_value(self[dst]);
+        }
+
+        // What stands on the square, for the next capture to win
+        let mut on_square = piece_value(self[orig]);
+        if let Some(promotion) = mov.promotion() {
+            on_square = piece_value(promotion);
+            gain[0] += on_square - piece_value(Piece::PawnW);
+        }
+
+        let mut side = self.to_move.flip();
+        let mut depth = 0;
+
+        loop {
+            let attackers = self.attackers(dst, side, occupied) & occupied;
+            let Some((piece, from)) = EXCHANGE_ORDER.iter().find_map(|piece| {
+                let piece = piece.to_color(side);
+                Bits(attackers & self.pieces(piece))
+                    .next()
+                    .map(|from| (piece, from))
+            }) else {
+                break;
+            };
+
+            depth += 1;
+            gain[depth] = on_square - gain[depth - 1];
+
+            // The capture loses whether or not it gets answered, so it isn't made
+            if (-gain[depth - 1]).max(gain[depth]) < 0 {
+                depth -= 1;
+                break;
+            }
+            if depth == gain.len() - 1 {
+                break;
+            }
+
+            occupied ^= bit(from);
+            on_square = piece_value(piece);
+            side = side.flip();
+        }
+
+        // Each side can stop capturing whenever carrying on would lose
+        while depth > 0 {
+            depth -= 1;
+            gain[depth] = -(-gain[depth]).max(gain[depth + 1]);
+        }
+
+        gain[0]
+    }
+
+    /// Whether [`Board::see`] of the move is at least `threshold`.
+    pub fn see_ge(&self, mov: Move, threshold: i32) -> bool {
+        self.see(mov) >= threshold
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Board;
+    use crate::moves::Move;
+
+    #[test]
+    fn test_see() {
+        let see = |fen, uci| {
+            let board = Board::from_fen(fen).unwrap();
+            board.see(Move::from_uci(uci, &board).unwrap())
+        };
+
+        // Undefended pawn
+        assert_eq!(
+            see("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"),
+            100
+        );
+        // Pawn defended by a knight, taken by a knight backed up by a rook and queen
+        assert_eq!(
+            see(
+                "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1",
+                "d3e5"
+            ),
+            -200
+        );
+        // Queen takes a defended pawn
+        assert_eq!(see("4k3/8/3p4/4p3/8/8/8/4QK2 w - - 0 1", "e1e5"), -800);
+        // The rook behind takes back, and the king can't join in on a defended square
+        assert_eq!(see("3rk3/8/8/8/8/8/3R4/3RK3 w - - 0 1", "d2d8"), 500);
+        // Two rooks for two rooks, with the king taking last
+        assert_eq!(see("3rk3/3r4/8/8/8/8/3R4/3RK3 w - - 0 1", "d2d7"), 0);
+        // The king can't take back on a defended square
+        assert_eq!(see("4k3/4p3/8/8/8/8/4R3/4RK2 w - - 0 1", "e2e7"), 100);
+        assert_eq!(see("4k3/8/8/4p3/3P4/8/8/4K3 w - - 0 1", "d4e5"), 100);
+        assert_eq!(see("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), 100);
+        // Quiet move onto a square the pawn covers
+        assert_eq!(see("4k3/8/2p5/8/2N5/8/8/4K3 w - - 0 1", "c4d6"), 0);
+        assert_eq!(see("4k3/8/2p5/8/4N3/8/8/4K3 w - - 0 1", "e4d6"), 0);
+        assert_eq!(see("4k3/8/4p3/8/5N2/8/8/4K3 w - - 0 1", "f4d5"), -300);
+        // Promotion
+        assert_eq!(see("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8q"), 800);
+        assert_eq!(see("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8q"), -100);
+    }
+}
@@ -0,0 +1,194 @@
+//! The transposition table, remembering what the search found out about positions it has seen.
+
+use crate::moves::Move;
+use crate::score::Score;
+
+/// The size of a new table in MB, as advertised over UCI.
+pub const DEFAULT_HASH_MB: usize = 16;
+
+/// How a stored score relates to the true score of the position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    /// The search failed high, so the true score is at least this.
+    Lower,
+    /// The search failed low, so the true score is at most this.
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TtEntry {
+    /// The full hash of the position, to tell it apart from others sharing its bucket.
+    pub hash: u64,
+    /// The best move found, or [`Move::NULL`] when every move failed low.
+    pub mov: Move,
+    pub depth: u8,
+    /// Relative to the position itself rather than the root, see [`Score::to_tt`].
+    pub score: Score,
+    pub bound: Bound,
+    /// The search the entry was stored in.
+    pub age: u8,
+}
+
+/// Each bucket has one slot that keeps the deepest result and one that keeps the latest.
+type Bucket = [Option<TtEntry>; 2];
+
+pub struct TranspositionTable {
+    buckets: Vec<Bucket>,
+    age: u8,
+}
+
+impl TranspositionTable {
+    pub fn new(mb: usize) -> Self {
+        let mut tt = Self {
+            buckets: Vec::new(),
+            age: 0,
+        };
+        tt.resize(mb);
+        tt
+    }
+
+    /// Changes the size of the table to roughly `mb` megabytes, forgetting everything in it.
+    pub fn resize(&mut self, mb: usize) {
+        let len = (mb * 1024 * 1024 / size_of::<Bucket>()).max(1);
+        self.buckets = vec![[None; 2]; len];
+        self.age = 0;
+    }
+
+    /// Empties every bucket and restarts the age count, keeping the size. Used on
+    /// `ucinewgame`, where entries from the last game would only crowd out new ones.
+    pub fn clear(&mut self) {
+        self.buckets.fill([None; 2]);
+        self.age = 0;
+    }
+
+    /// Marks the start of a new search, so entries from older ones get replaced first.
+    pub fn new_search(&mut self) {
+        self.age = self.age.wrapping_add(1);
+    }
+
+    fn bucket(&self, hash: u64) -> usize {
+        ((hash as u128 * self.buckets.len() as u128) >> 64) as usize
+    }
+
+    pub fn probe(&self, hash: u64) -> Option<TtEntry> {
+        self.buckets[self.bucket(hash)]
+            .into_iter()
+            .flatten()
+            .find(|entry| entry.hash == hash)
+    }
+
+    pub fn store(&mut self, hash: u64, mov: Move, depth: u8, score: Score, bound: Bound) {
+        let age = self.age;
+        let index = self.bucket(hash);
+        let [deepest, latest] = &mut self.buckets[index];
+
+        let mut entry = TtEntry {
+            hash,
+            mov,
+            depth,
+            score,
+            bound,
+            age,
+        };
+
+        // Keep the best move of a position when searching it again didn't find one
+        if let Some(old) = deepest
+            .iter()
+            .chain(latest.iter())
+            .find(|old| old.hash == hash)
+            && entry.mov == Move::NULL
+        {
+            entry.mov = old.mov;
+        }
+
+        match deepest {
+            Some(old) if old.age == age && old.depth > depth && old.hash != hash => {
+                *latest = Some(entry);
+            }
+            _ => {
+                // The same position can't take both slots
+                if latest.is_some_and(|old| old.hash == hash) {
+                    *latest = None;
+                }
+                *deepest = Some(entry);
+            }
+        }
+    }
+
+    /// Roughly how full the table is with entries from the current search, in permille.
+    pub fn hashfull(&self) -> usize {
+        let sample = &self.buckets[..self.buckets.len().min(500)];
+        let used = sample
+            .iter()
+            .flatten()
+            .flatten()
+            .filter(|entry| entry.age == self.age)
+            .count();
+
+        used * 1000 / (sample.len() * 2)
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new(DEFAULT_HASH_MB)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bound, TranspositionTable};
+    use crate::Board;
+    use crate::moves::{Move, gen_moves};
+    use crate::score::Score;
+
+    #[test]
+    fn test_store_probe() {
+        let mut tt = TranspositionTable::new(1);
+        let board = Board::start_pos();
+        let mov = gen_moves(&board)[0];
+
+        assert_eq!(tt.probe(board.hash()), None);
+        tt.store(board.hash(), mov, 3, Score::cp(20), Bound::Exact);
+
+        let entry = tt.probe(board.hash()).unwrap();
+        assert_eq!(entry.mov, mov);
+        assert_eq!(entry.depth, 3);
+        assert_eq!(entry.score, Score::cp(20));
+        assert_eq!(entry.bound, Bound::Exact);
+
+        // A fail low keeps the move from before
+        tt.store(board.hash(), Move::NULL, 4, Score::cp(-5), Bound::Upper);
+        let entry = tt.probe(board.hash()).unwrap();
+        assert_eq!(entry.mov, mov);
+        assert_eq!(entry.depth, 4);
+
+        tt.clear();
+        assert_eq!(tt.probe(board.hash()), None);
+    }
+
+    #[test]
+    fn test_replacement() {
+        // A single bucket, so every hash collides
+        let mut tt = TranspositionTable::new(0);
+
+        tt.store(1, Move::NULL, 5, Score::cp(1), Bound::Exact);
+        tt.store(2, Move::NULL, 2, Score::cp(2), Bound::Exact);
+        tt.store(3, Move::NULL, 3, Score::cp(3), Bound::Exact);
+        // The deep entry survives, the shallow one gets replaced
+        assert!(tt.probe(1).is_some());
+        assert!(tt.probe(2).is_none());
+        assert!(tt.probe(3).is_some());
+        assert_eq!(tt.hashfull(), 1000);
+
+        // Entries from an old search give way even when deeper
+        tt.new_search();
+        assert_eq!(tt.hashfull(), 0);
+        tt.store(4, Move::NULL, 1, Score::cp(4), Bound::Exact);
+        assert!(tt.probe(1).is_none());
+        assert!(tt.probe(3).is_some());
+        assert!(tt.probe(4).is_some());
+        assert_eq!(tt.hashfull(), 500);
+    }
+}
@@ -0,0 +1,64 @@
+//! Random keys for Zobrist hashing. They are generated at compile time from a fixed seed, so
+//! hashes are stable between runs.
+
+pub(crate) struct Keys {
+    /// Indexed by `Piece as usize` and the 0x88 index of the square.
+    pub pieces: [[u64; 128]; 16],
+    pub castling: [u64; 4],
+    /// Indexed by the file of the en passant target.
+    pub en_passant: [u64; 8],
+    pub black_to_move: u64,
+}
+
+pub(crate) static KEYS: Keys = Keys::generate();
+
+const fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+impl Keys {
+    const fn generate() -> Self {
+        let mut state = 0x4855_5343_4545_u64;
+
+        let mut pieces = [[0; 128]; 16];
+        let mut p = 0;
+        while p < 16 {
+            let mut sq = 0;
+            while sq < 128 {
+                // Only fill in the squares that are actually on the board
+                if sq & 0x88 == 0 {
+                    pieces[p][sq] = splitmix64(&mut state);
+                }
+                sq += 1;
+            }
+            p += 1;
+        }
+
+        let mut castling = [0; 4];
+        let mut i = 0;
+        while i < 4 {
+            castling[i] = splitmix64(&mut state);
+            i += 1;
+        }
+
+        let mut en_passant = [0; 8];
+        let mut i = 0;
+        while i < 8 {
+            en_passant[i] = splitmix64(&mut state);
+            i += 1;
+        }
+
+        let black_to_move = splitmix64(&mut state);
+
+        Self {
+            pieces,
+            castling,
+            en_passant,
+            black_to_move,
+        }
+    }
+}